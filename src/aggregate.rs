@@ -0,0 +1,161 @@
+use crate::delimiter::Delimiter;
+use crate::items::Items;
+
+/// Binary size suffixes, as printed by "du -h" or "ls -lh".
+const SIZE_SUFFIXES: [char; 4] = ['K', 'M', 'G', 'T'];
+
+/// Numeric values of a field of the input lines.
+pub struct FieldValues {
+    values: Vec<Option<f64>>,
+    /// Whether some value was written with a size suffix, in which case results are too.
+    sizes: bool,
+}
+
+impl FieldValues {
+    /// Reads the field (numbered from 1) of each line, lines where it is missing or not a
+    /// number have no value.
+    pub fn read(items: &dyn Items, delimiter: &Delimiter, field: usize) -> FieldValues {
+        let mut sizes = false;
+        let values = (0..items.len())
+            .map(|idx| {
+                let line = items.get(idx);
+                let text = delimiter.split(&line).get(field - 1)?.trim().to_string();
+                let (value, sized) = parse_quantity(&text)?;
+                sizes |= sized;
+                Some(value)
+            })
+            .collect();
+        FieldValues { values, sizes }
+    }
+
+    /// Returns the value of the line at the provided index.
+    pub fn get(&self, idx: usize) -> Option<f64> {
+        self.values.get(idx).copied().flatten()
+    }
+
+    /// Returns the sum of the values of the provided lines and how many of them have one.
+    pub fn sum(&self, indices: impl Iterator<Item = usize>) -> (f64, usize) {
+        indices
+            .filter_map(|idx| self.get(idx))
+            .fold((0.0, 0), |(sum, count), value| (sum + value, count + 1))
+    }
+
+    /// Returns the value written as the field values are, with a size suffix if they have one.
+    pub fn format(&self, value: f64) -> String {
+        if !self.sizes {
+            return format_number(value);
+        }
+        let mut scaled = value;
+        let mut suffix = None;
+        for s in SIZE_SUFFIXES {
+            if scaled.abs() < 1024.0 {
+                break;
+            }
+            scaled /= 1024.0;
+            suffix = Some(s);
+        }
+        match suffix {
+            Some(suffix) => format!("{}{suffix}", format_number(scaled)),
+            None => format_number(scaled),
+        }
+    }
+}
+
+/// Parses a number, optionally followed by a size suffix (e.g., "1.5K", "20MB" or "3GiB"),
+/// returning its value and whether it had a suffix.
+pub fn parse_quantity(text: &str) -> Option<(f64, bool)> {
+    let number = text.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let unit = text[number.len()..].to_ascii_uppercase();
+    let unit = unit
+        .strip_suffix("IB")
+        .or(unit.strip_suffix('B'))
+        .unwrap_or(&unit);
+    let value: f64 = number.replace(',', "").parse().ok()?;
+    if unit.is_empty() {
+        return Some((value, false));
+    }
+    let mut chars = unit.chars();
+    let (Some(suffix), None) = (chars.next(), chars.next()) else {
+        return None;
+    };
+    let exponent = SIZE_SUFFIXES.iter().position(|&s| s == suffix)? as i32 + 1;
+    Some((value * 1024_f64.powi(exponent), true))
+}
+
+/// Returns the number with at most two decimals, none if it is whole.
+fn format_number(value: f64) -> String {
+    let text = format!("{value:.2}");
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// Limit on the total of a numeric field over the selected entries, parsed from
+/// "field=N,max=LIMIT".
+#[derive(Clone)]
+pub struct BudgetSpec {
+    field: usize,
+    max: f64,
+    sized: bool,
+}
+
+/// Parses the budget from its "field=N,max=LIMIT" argument, where the limit may have a size
+/// suffix (e.g., "field=3,max=500M").
+pub fn parse_budget(arg: &str) -> Result<BudgetSpec, String> {
+    let mut field = None;
+    let mut max = None;
+    for part in arg.split(',') {
+        match part.split_once('=') {
+            Some(("field", n)) => match n.parse() {
+                Ok(0) | Err(_) => {
+                    return Err(format!("invalid field '{n}', numbering starts at 1"))
+                }
+                Ok(n) => field = Some(n),
+            },
+            Some(("max", limit)) => {
+                max = Some(parse_quantity(limit).ok_or(format!("invalid limit '{limit}'"))?)
+            }
+            _ => {
+                return Err(format!(
+                    "invalid budget '{part}', expected field=N or max=LIMIT"
+                ))
+            }
+        }
+    }
+    match (field, max) {
+        (Some(field), Some((max, sized))) => Ok(BudgetSpec { field, max, sized }),
+        _ => Err(String::from("both field=N and max=LIMIT are required")),
+    }
+}
+
+/// Numeric field whose total over the selected entries may not exceed a limit, lines
+/// without a value counting as 0.
+pub struct Budget {
+    values: FieldValues,
+    max: f64,
+}
+
+impl Budget {
+    /// Reads the budgeted field of each line.
+    pub fn new(spec: &BudgetSpec, items: &dyn Items, delimiter: &Delimiter) -> Budget {
+        let mut values = FieldValues::read(items, delimiter, spec.field);
+        values.sizes |= spec.sized;
+        Budget {
+            values,
+            max: spec.max,
+        }
+    }
+
+    /// Returns the value of the line at the provided index.
+    pub fn cost(&self, idx: usize) -> f64 {
+        self.values.get(idx).unwrap_or(0.0)
+    }
+
+    /// Returns the budget left once the provided lines are selected.
+    pub fn remaining(&self, selected: impl Iterator<Item = usize>) -> f64 {
+        self.max - self.values.sum(selected).0
+    }
+
+    /// Returns the value written as the field values or limit are.
+    pub fn format(&self, value: f64) -> String {
+        self.values.format(value)
+    }
+}
@@ -0,0 +1,178 @@
+use std::borrow::Cow;
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Returns the text without escape sequences.
+pub fn strip(text: &str) -> String {
+    filter_escapes(text, false)
+}
+
+/// Returns the text keeping only its SGR sequences (colors and attributes), other escape
+/// sequences (e.g., cursor movements) are removed.
+pub fn keep_sgr(text: &str) -> String {
+    filter_escapes(text, true)
+}
+
+/// Returns the text made safe to display: without escape sequences and with the other
+/// control characters made visible in caret notation (e.g., "^M" for a carriage return),
+/// tabs being replaced by a space.
+pub fn sanitize(text: &str) -> String {
+    if !text.contains(|c: char| c.is_ascii_control()) {
+        return text.to_string();
+    }
+    visualize_controls(&strip(text))
+}
+
+/// Returns the text with the control characters other than escapes made visible in caret
+/// notation, tabs being replaced by a space.
+pub fn visualize_controls(text: &str) -> String {
+    let mut visible = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\t' => visible.push(' '),
+            '\x1b' => visible.push(c),
+            '\x7f' => visible.push_str("^?"),
+            c if c.is_ascii_control() => {
+                visible.push('^');
+                visible.push((c as u8 + b'@') as char);
+            }
+            c => visible.push(c),
+        }
+    }
+    visible
+}
+
+/// Returns the text without escape sequences, keeping SGR sequences (CSI ending with "m")
+/// if `keep_sgr` is set.
+fn filter_escapes(text: &str, keep_sgr: bool) -> String {
+    let mut filtered = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('\x1b') {
+        filtered.push_str(&rest[..start]);
+        let len = escape_len(&rest[start..]);
+        let sequence = &rest[start..start + len];
+        if keep_sgr && sequence.starts_with("\x1b[") && sequence.ends_with('m') {
+            filtered.push_str(sequence);
+        }
+        rest = &rest[start + len..];
+    }
+    filtered.push_str(rest);
+    filtered
+}
+
+/// Returns the length of the escape sequence the text starts with: CSI ("ESC [" up to a
+/// final byte), OSC ("ESC ]" up to BEL or "ESC \") or two characters, up to the end of the
+/// text if it is unterminated.
+fn escape_len(text: &str) -> usize {
+    let mut chars = text.char_indices().skip(1).peekable();
+    let end = match chars.next() {
+        Some((_, '[')) => chars.find(|&(_, c)| ('\x40'..='\x7e').contains(&c)),
+        Some((_, ']')) => loop {
+            match chars.next() {
+                Some((i, '\x07')) => break Some((i, '\x07')),
+                Some((_, '\x1b')) if chars.peek().is_some_and(|&(_, c)| c == '\\') => {
+                    break chars.next()
+                }
+                Some(_) => {}
+                None => break None,
+            }
+        },
+        other => other,
+    };
+    end.map_or(text.len(), |(i, c)| i + c.len_utf8())
+}
+
+/// Returns the text cut to the provided display width (counting wide characters as two
+/// columns), ending with "…" if it is wider. Escape sequences take no width and are all
+/// kept, so that the styles they set are reset as in the whole text.
+pub fn truncate(text: &str, width: usize) -> Cow<'_, str> {
+    if visible_width(text) <= width {
+        return Cow::Borrowed(text);
+    }
+    let mut truncated = String::with_capacity(text.len());
+    let mut used = 0;
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if c == '\x1b' {
+            let len = escape_len(rest);
+            truncated.push_str(&rest[..len]);
+            rest = &rest[len..];
+            continue;
+        }
+        let char_width = c.width().unwrap_or(0);
+        if used + char_width < width {
+            truncated.push(c);
+            used += char_width;
+        } else {
+            used = width;
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+    if width > 0 {
+        truncated.push('…');
+    }
+    Cow::Owned(truncated)
+}
+
+/// Returns the text without the characters of its first `width` columns (a wide character
+/// across the limit is removed too), keeping all escape sequences.
+pub fn skip_width(text: &str, width: usize) -> Cow<'_, str> {
+    if width == 0 {
+        return Cow::Borrowed(text);
+    }
+    let mut kept = String::with_capacity(text.len());
+    let mut skipped = 0;
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if c == '\x1b' {
+            let len = escape_len(rest);
+            kept.push_str(&rest[..len]);
+            rest = &rest[len..];
+            continue;
+        }
+        if skipped < width {
+            skipped += c.width().unwrap_or(0);
+        } else {
+            kept.push(c);
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+    Cow::Owned(kept)
+}
+
+/// Returns the rows of the text wrapped at the provided display width, the rows after the
+/// first being indented by `indent` spaces. Escape sequences are kept in the row they appear
+/// in, so styles carry over to the following rows as the terminal keeps them.
+pub fn wrap(text: &str, width: usize, indent: usize) -> Vec<String> {
+    let mut rows = Vec::new();
+    let mut row = String::new();
+    let mut used = 0;
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if c == '\x1b' {
+            let len = escape_len(rest);
+            row.push_str(&rest[..len]);
+            rest = &rest[len..];
+            continue;
+        }
+        let char_width = c.width().unwrap_or(0);
+        if used + char_width > width && used > indent {
+            rows.push(std::mem::replace(&mut row, " ".repeat(indent)));
+            used = indent;
+        }
+        row.push(c);
+        used += char_width;
+        rest = &rest[c.len_utf8()..];
+    }
+    rows.push(row);
+    rows
+}
+
+/// Returns the display width of the text, ignoring escape sequences.
+pub fn visible_width(text: &str) -> usize {
+    if text.contains('\x1b') {
+        strip(text).width()
+    } else {
+        text.width()
+    }
+}
@@ -0,0 +1,28 @@
+use unicode_bidi::{bidi_class, BidiClass, BidiInfo};
+use unicode_width::UnicodeWidthStr;
+
+/// Returns true if most of the strongly directional characters in the text are right-to-left
+/// (e.g., Arabic or Hebrew).
+pub fn is_rtl_dominant(text: &str) -> bool {
+    let (rtl, ltr) = text
+        .chars()
+        .fold((0, 0), |(rtl, ltr), c| match bidi_class(c) {
+            BidiClass::R | BidiClass::AL => (rtl + 1, ltr),
+            BidiClass::L => (rtl, ltr + 1),
+            _ => (rtl, ltr),
+        });
+    rtl > ltr
+}
+
+/// Returns the text reordered for display following the Unicode bidirectional algorithm,
+/// right-aligned to the provided width.
+pub fn render_rtl(text: &str, width: usize) -> String {
+    let info = BidiInfo::new(text, None);
+    let visual: String = info
+        .paragraphs
+        .iter()
+        .map(|p| info.reorder_line(p, p.range.clone()))
+        .collect();
+    let padding = width.saturating_sub(visual.width());
+    format!("{}{}", " ".repeat(padding), visual)
+}
@@ -0,0 +1,54 @@
+use std::collections::VecDeque;
+
+/// Text shown in the preview area for an entry.
+#[derive(Clone)]
+pub struct Preview {
+    pub text: String,
+    /// Whether the text describes a failure of the preview command, shown as an error.
+    pub failed: bool,
+}
+
+/// Outputs of the preview command for the last entries previewed, the least recently used
+/// one being evicted when the cache is full.
+pub struct PreviewCache {
+    capacity: usize,
+    /// Cached outputs by entry index, most recently used first.
+    outputs: VecDeque<(usize, Preview)>,
+}
+
+impl PreviewCache {
+    /// Create new empty `PreviewCache` keeping the output of up to `capacity` entries.
+    pub fn new(capacity: usize) -> PreviewCache {
+        PreviewCache {
+            capacity,
+            outputs: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the cached output of the entry, marking it as the most recently used.
+    pub fn get(&mut self, entry: usize) -> Option<&Preview> {
+        let position = self.outputs.iter().position(|&(e, _)| e == entry)?;
+        let cached = self.outputs.remove(position)?;
+        self.outputs.push_front(cached);
+        self.outputs.front().map(|(_, output)| output)
+    }
+
+    /// Caches the output of the entry, evicting the least recently used one if full.
+    pub fn insert(&mut self, entry: usize, output: Preview) {
+        self.remove(entry);
+        if self.outputs.len() >= self.capacity {
+            self.outputs.pop_back();
+        }
+        self.outputs.push_front((entry, output));
+    }
+
+    /// Drops the cached output of the entry, so it is generated again.
+    pub fn remove(&mut self, entry: usize) {
+        self.outputs.retain(|&(e, _)| e != entry);
+    }
+
+    /// Drops every cached output.
+    pub fn clear(&mut self) {
+        self.outputs.clear();
+    }
+}
@@ -0,0 +1,224 @@
+use std::env;
+use std::error::Error;
+use std::io::{Read, Write};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Command template with `{}` placeholders, replaced by the item the command is run for, or
+/// `{+}` placeholders, replaced by all the items it is run for.
+pub struct Template {
+    template: String,
+    use_shell: bool,
+}
+
+impl Template {
+    /// Create new `Template`, run through `$SHELL -c` unless `use_shell` is false, in which
+    /// case the template is split on whitespace and executed directly.
+    pub fn new(template: &str, use_shell: bool) -> Template {
+        Template {
+            template: template.to_string(),
+            use_shell,
+        }
+    }
+
+    /// Returns the command as written, placeholders included, for commands that are not run
+    /// for an item (e.g., source commands).
+    pub fn plain_command(&self) -> Command {
+        if self.use_shell {
+            let mut cmd =
+                Command::new(env::var("SHELL").unwrap_or_else(|_| String::from("/bin/sh")));
+            cmd.arg("-c").arg(&self.template);
+            cmd
+        } else {
+            let mut args = self.template.split_whitespace();
+            let mut cmd = Command::new(args.next().unwrap_or_default());
+            cmd.args(args);
+            cmd
+        }
+    }
+
+    /// Returns the command resulting from replacing the placeholders with the provided item.
+    pub fn command(&self, item: &str) -> Command {
+        self.substitute("{}", item, false)
+    }
+
+    /// Returns the command resulting from replacing the placeholders with the provided item,
+    /// which is appended as last argument if the template has no placeholder.
+    pub fn item_command(&self, item: &str) -> Command {
+        self.substitute("{}", item, !self.template.contains("{}"))
+    }
+
+    /// Returns the command resulting from replacing the "{+}" placeholders with the provided
+    /// items as separate words, which are appended as last arguments if the template has no
+    /// such placeholder.
+    pub fn multi_command(&self, items: &[String]) -> Command {
+        let append = !self.template.contains("{+}");
+        if self.use_shell {
+            let words: Vec<String> = items.iter().map(|i| shell_quote(i)).collect();
+            let words = words.join(" ");
+            let mut script = self.template.replace("{+}", &words);
+            if append {
+                script = format!("{script} {words}");
+            }
+            let mut cmd =
+                Command::new(env::var("SHELL").unwrap_or_else(|_| String::from("/bin/sh")));
+            cmd.arg("-c").arg(script);
+            cmd
+        } else {
+            let mut parts = self.template.split_whitespace();
+            let mut cmd = Command::new(parts.next().unwrap_or_default());
+            for part in parts {
+                if part == "{+}" {
+                    cmd.args(items);
+                } else {
+                    cmd.arg(part);
+                }
+            }
+            if append {
+                cmd.args(items);
+            }
+            cmd
+        }
+    }
+
+    /// Returns the command resulting from replacing the "{q}" placeholders with the provided
+    /// query, which is appended as last argument if the template has no such placeholder.
+    pub fn query_command(&self, query: &str) -> Command {
+        self.substitute("{q}", query, !self.template.contains("{q}"))
+    }
+
+    /// Returns the command with the placeholder replaced by the value, optionally appending
+    /// the value as an extra argument.
+    fn substitute(&self, placeholder: &str, value: &str, append: bool) -> Command {
+        if self.use_shell {
+            let shell = env::var("SHELL").unwrap_or_else(|_| String::from("/bin/sh"));
+            let mut script = self.template.replace(placeholder, &shell_quote(value));
+            if append {
+                script = format!("{script} {}", shell_quote(value));
+            }
+            let mut cmd = Command::new(shell);
+            cmd.arg("-c").arg(script);
+            cmd
+        } else {
+            let mut args = self
+                .template
+                .split_whitespace()
+                .map(|a| a.replace(placeholder, value));
+            let mut cmd = Command::new(args.next().unwrap_or_default());
+            cmd.args(args);
+            if append {
+                cmd.arg(value);
+            }
+            cmd
+        }
+    }
+}
+
+/// Kills the process group with the provided id, i.e., a command started by `Running` along
+/// with the processes it started.
+pub fn kill_group(pgid: u32) {
+    // SAFETY: kill has no memory effects, the group was created with the child's pid
+    unsafe { libc::kill(-(pgid as libc::pid_t), libc::SIGKILL) };
+}
+
+/// Command running in a process group of its own, its output being read in the background.
+pub struct Running {
+    child: Child,
+    stdout: Receiver<Vec<u8>>,
+    stderr: Receiver<Vec<u8>>,
+}
+
+impl Running {
+    /// Starts the command in a process group of its own, with the provided input (if any)
+    /// on stdin.
+    pub fn spawn(mut cmd: Command, input: Option<String>) -> Result<Running, Box<dyn Error>> {
+        let stdin = if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        };
+        let mut child = cmd
+            .process_group(0)
+            .stdin(stdin)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let (Some(mut stdin), Some(input)) = (child.stdin.take(), input) {
+            thread::spawn(move || {
+                // the command may exit without reading all its input
+                let _ = writeln!(stdin, "{input}");
+            });
+        }
+        let stdout = read_in_background(child.stdout.take().ok_or("no stdout")?);
+        let stderr = read_in_background(child.stderr.take().ok_or("no stderr")?);
+        Ok(Running {
+            child,
+            stdout,
+            stderr,
+        })
+    }
+
+    /// Returns the id of the process group of the command, that of the command itself.
+    pub fn pgid(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// Waits for the command to exit, returning its exit status and output. If it has not
+    /// exited within the timeout, its whole group is killed.
+    pub fn wait(mut self, timeout: Duration) -> Result<Output, Box<dyn Error>> {
+        let deadline = Instant::now() + timeout;
+        let stdout = self.stdout.recv_timeout(timeout);
+        // the output may be complete while the command is still running
+        let status = loop {
+            if let Some(status) = self.child.try_wait()? {
+                break Some(status);
+            }
+            if Instant::now() >= deadline {
+                kill_group(self.pgid());
+                self.child.wait()?;
+                break None;
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        let (Some(status), Ok(stdout)) = (status, stdout) else {
+            return Err(format!("timed out after {}s", timeout.as_secs_f64()).into());
+        };
+        let stderr = self
+            .stderr
+            .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            .unwrap_or_default();
+        Ok(Output {
+            status,
+            stdout,
+            stderr,
+        })
+    }
+}
+
+/// Reads the stream to its end in a thread, returning the channel receiving its content.
+fn read_in_background(mut stream: impl Read + Send + 'static) -> Receiver<Vec<u8>> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut content = Vec::new();
+        let _ = stream.read_to_end(&mut content);
+        let _ = tx.send(content);
+    });
+    rx
+}
+
+/// Returns how the process ended, e.g., "exit status 2".
+pub fn describe_status(status: ExitStatus) -> String {
+    match status.code() {
+        Some(code) => format!("exit status {code}"),
+        None => String::from("killed by a signal"),
+    }
+}
+
+/// Returns the provided string wrapped in single quotes, escaping any single quote in it,
+/// so it is passed to the shell as a single literal word.
+pub fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
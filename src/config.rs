@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+/// Settings read from the configuration file.
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Named sources listed with `tui_selector @NAME`.
+    #[serde(default)]
+    pub sources: HashMap<String, Source>,
+    /// Characters pointing at the entry under the cursor.
+    pub pointer: Option<String>,
+    /// Characters shown before the selected entries.
+    pub marker: Option<String>,
+    /// Show a checkbox before each entry.
+    #[serde(default)]
+    pub checkboxes: bool,
+    /// Colors of the selector elements, as given to `--color`.
+    pub color: Option<String>,
+    /// Stop the cursor at the ends of the list instead of wrapping around.
+    #[serde(default)]
+    pub no_cycle: bool,
+    /// Entries of the action menu opened on the entry under the cursor.
+    #[serde(default)]
+    pub menu: Vec<MenuEntry>,
+}
+
+/// Operation offered for the entry under the cursor in the action menu.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MenuEntry {
+    /// Name shown in the menu.
+    pub label: String,
+    /// Key picking the entry while the menu is open.
+    pub key: Option<char>,
+    /// Command run for the entry, "{}" being replaced by it (appended if missing).
+    pub cmd: Option<String>,
+    /// Action run instead of a command, named as with `--bind`.
+    pub action: Option<String>,
+    /// Show the output of the command over the list.
+    #[serde(default)]
+    pub show: bool,
+}
+
+/// Command producing a list, along with how to display it.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Source {
+    /// Command whose output lines are listed.
+    pub cmd: String,
+    /// Delimiter between the fields of each line.
+    pub delimiter: Option<String>,
+    /// Fields displayed in the selector (numbered from 1), all if missing.
+    pub columns: Option<Vec<usize>>,
+}
+
+impl Config {
+    /// Read the configuration file, empty if it does not exist.
+    pub fn load() -> Result<Config, Box<dyn Error>> {
+        let Some(path) = config_path() else {
+            return Ok(Config::default());
+        };
+        match fs::read_to_string(&path) {
+            Ok(content) => {
+                toml::from_str(&content).map_err(|e| format!("{}: {e}", path.display()).into())
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(format!("{}: {e}", path.display()).into()),
+        }
+    }
+}
+
+/// Returns the path of the configuration file: `$TUI_SELECTOR_CONFIG`, or "tui_selector/config.toml"
+/// in `$XDG_CONFIG_HOME` (`~/.config` if unset).
+pub fn config_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("TUI_SELECTOR_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("tui_selector").join("config.toml"))
+}
@@ -0,0 +1,97 @@
+use regex::Regex;
+
+/// Separator between the fields of an input line, either a literal string, a regex or the
+/// boundaries of whitespace-aligned columns.
+#[derive(Clone)]
+pub enum Delimiter {
+    Literal(String),
+    Pattern(Regex),
+    /// Character positions where each column starts.
+    Columns(Vec<usize>),
+}
+
+impl Delimiter {
+    /// Create new `Delimiter` from the provided string, compiling it if `is_regex` is set.
+    /// Literal delimiters support the "\t" escape for tab separated input.
+    pub fn new(delimiter: &str, is_regex: bool) -> Result<Delimiter, regex::Error> {
+        if is_regex {
+            Ok(Delimiter::Pattern(Regex::new(delimiter)?))
+        } else {
+            Ok(Delimiter::Literal(delimiter.replace("\\t", "\t")))
+        }
+    }
+
+    /// Returns all the fields of the provided line.
+    pub fn split<'a>(&self, line: &'a str) -> Vec<&'a str> {
+        match self {
+            Delimiter::Literal(d) => line.split(d.as_str()).collect(),
+            Delimiter::Pattern(re) => re.split(line).collect(),
+            Delimiter::Columns(starts) => {
+                let offsets = column_offsets(line, starts);
+                let ends = offsets.iter().skip(1).copied().chain([line.len()]);
+                offsets
+                    .iter()
+                    .zip(ends)
+                    .map(|(&s, e)| line[s..e].trim())
+                    .collect()
+            }
+        }
+    }
+
+    /// Returns the text separating fields in the provided line, which for regex delimiters
+    /// is the first match in the line (a single space if there is none) and two spaces for
+    /// columns.
+    pub fn separator<'a>(&'a self, line: &'a str) -> &'a str {
+        match self {
+            Delimiter::Literal(d) => d,
+            Delimiter::Pattern(re) => re.find(line).map_or(" ", |m| m.as_str()),
+            Delimiter::Columns(_) => "  ",
+        }
+    }
+
+    /// Splits the provided line on the first occurrence of the delimiter, returns `None` if
+    /// the delimiter is not found.
+    pub fn split_once<'a>(&self, line: &'a str) -> Option<(&'a str, &'a str)> {
+        match self {
+            Delimiter::Literal(d) => line.split_once(d.as_str()),
+            Delimiter::Pattern(re) => re
+                .find(line)
+                .map(|m| (&line[..m.start()], &line[m.end()..])),
+            Delimiter::Columns(starts) => {
+                let offsets = column_offsets(line, starts);
+                let second = *offsets.get(1)?;
+                Some((line[..second].trim(), line[second..].trim()))
+            }
+        }
+    }
+}
+
+/// Returns the byte offsets in the line of the column starts (character positions) it reaches.
+fn column_offsets(line: &str, starts: &[usize]) -> Vec<usize> {
+    let mut chars = line.char_indices().map(|(i, _)| i).enumerate();
+    starts
+        .iter()
+        .map_while(|&start| {
+            chars
+                .find(|&(pos, _)| pos == start)
+                .map(|(_, offset)| offset)
+        })
+        .collect()
+}
+
+/// Returns the character positions where whitespace-aligned columns start in the provided
+/// lines, i.e., where some line has a non-blank character after a position blank in all.
+pub fn detect_columns<S: AsRef<str>>(lines: &[S]) -> Vec<usize> {
+    let mut filled: Vec<bool> = Vec::new();
+    for line in lines {
+        for (pos, c) in line.as_ref().chars().enumerate() {
+            if pos >= filled.len() {
+                filled.resize(pos + 1, false);
+            }
+            filled[pos] |= !c.is_whitespace();
+        }
+    }
+    let mut starts = vec![0];
+    starts.extend((1..filled.len()).filter(|&pos| filled[pos] && !filled[pos - 1]));
+    starts
+}
@@ -0,0 +1,56 @@
+use std::collections::HashSet;
+
+/// List a line of the compared lists is found in.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Side {
+    /// Only in the first list.
+    Removed,
+    /// Only in the second list.
+    Added,
+    /// In both lists.
+    Common,
+}
+
+impl Side {
+    /// Returns the character marking the lines of this side, as in `diff`'s normal output.
+    pub fn mark(self) -> char {
+        match self {
+            Side::Removed => '<',
+            Side::Added => '>',
+            Side::Common => '=',
+        }
+    }
+}
+
+/// Returns the non-blank lines of both lists, those of the first one in their order followed
+/// by those only in the second one, along with the list each of them is found in.
+pub fn merge(first: &str, second: &str) -> (Vec<String>, Vec<Side>) {
+    let lines = |text: &str| -> Vec<String> {
+        text.lines()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect()
+    };
+    let (first, second) = (lines(first), lines(second));
+    let in_first: HashSet<&str> = first.iter().map(String::as_str).collect();
+    let in_second: HashSet<&str> = second.iter().map(String::as_str).collect();
+    let mut sides: Vec<Side> = first
+        .iter()
+        .map(|l| {
+            if in_second.contains(l.as_str()) {
+                Side::Common
+            } else {
+                Side::Removed
+            }
+        })
+        .collect();
+    let added: Vec<String> = second
+        .iter()
+        .filter(|l| !in_first.contains(l.as_str()))
+        .cloned()
+        .collect();
+    sides.extend(added.iter().map(|_| Side::Added));
+    let mut merged = first;
+    merged.extend(added);
+    (merged, sides)
+}
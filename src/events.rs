@@ -0,0 +1,371 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::iter;
+use std::os::fd::AsRawFd;
+use std::process::Output;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use signal_hook::consts::{SIGUSR1, SIGWINCH};
+use signal_hook::iterator::Signals;
+use termion::event::{self as term_event, Key};
+
+use crate::keymap::{key_name, parse_key};
+use crate::theme::{Background, CursorShape};
+
+/// Events handled by the selector loop.
+pub enum Event {
+    Key(Key),
+    /// Request to reload the display settings, sent on SIGUSR1.
+    Reload,
+    /// The terminal was resized, sent on SIGWINCH.
+    Resize,
+    /// Terminal reply to a background color query.
+    Background(Background),
+    /// Terminal reply to a cursor shape query.
+    CursorShape(CursorShape),
+    /// Line written to the append FIFO, added to the list.
+    Append(String),
+    /// Command run in the background exited (or failed to run), with its process group id.
+    JobDone(u32, Result<Output, String>),
+    /// The replayed recording ended.
+    End,
+}
+
+/// Sending end of the channel receiving the events handled by the selector loop.
+pub type EventSender = Sender<Result<Event, io::Error>>;
+
+impl Event {
+    /// Returns the event as written in recordings, `None` if it cannot be replayed.
+    fn to_record(&self) -> Option<String> {
+        match self {
+            Event::Key(key) => {
+                let name = key_name(*key);
+                (parse_key(&name) == Some(*key)).then(|| format!("key {name}"))
+            }
+            Event::Reload => Some(String::from("reload")),
+            Event::Resize => Some(String::from("resize")),
+            Event::Background(Background::Dark) => Some(String::from("background dark")),
+            Event::Background(Background::Light) => Some(String::from("background light")),
+            // the shape to restore is that of the terminal replaying, not of the recording one
+            Event::CursorShape(_) => None,
+            Event::Append(line) => Some(format!("append {line}")),
+            // the commands run again when replaying
+            Event::JobDone(..) | Event::End => None,
+        }
+    }
+
+    /// Returns the event written in a recording.
+    fn from_record(record: &str) -> Option<Event> {
+        match record.split_once(' ') {
+            Some(("key", name)) => parse_key(name).map(Event::Key),
+            Some(("background", "dark")) => Some(Event::Background(Background::Dark)),
+            Some(("background", "light")) => Some(Event::Background(Background::Light)),
+            Some(("append", line)) => Some(Event::Append(line.to_string())),
+            None if record == "reload" => Some(Event::Reload),
+            None if record == "resize" => Some(Event::Resize),
+            _ => None,
+        }
+    }
+}
+
+/// Writes the events received by the selector to a file, one per line after the
+/// milliseconds elapsed since the recording started (e.g., "1520 key down").
+pub struct Recorder {
+    file: File,
+    start: Instant,
+}
+
+impl Recorder {
+    /// Create new `Recorder` writing to the file at the provided path.
+    pub fn create(path: &str) -> Result<Recorder, Box<dyn Error>> {
+        Ok(Recorder {
+            file: File::create(path)?,
+            start: Instant::now(),
+        })
+    }
+
+    /// Appends the event to the recording, skipping events that cannot be replayed.
+    pub fn record(&mut self, event: &Event) -> Result<(), io::Error> {
+        if let Some(record) = event.to_record() {
+            writeln!(self.file, "{} {record}", self.start.elapsed().as_millis())?;
+        }
+        Ok(())
+    }
+}
+
+/// Amount of terminal queries sent whose reply has not been read yet.
+static PENDING_REPLIES: AtomicUsize = AtomicUsize::new(0);
+
+/// Time waited for the rest of a sequence after an escape byte read last while replies are
+/// pending, before reading it as the escape key.
+const REPLY_WAIT: Duration = Duration::from_millis(50);
+
+/// Notes that a query was sent to the terminal, so that the escape byte starting its reply
+/// is not read as the escape key if the reply is split across reads.
+pub fn expect_reply() {
+    PENDING_REPLIES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Reads key presses and terminal replies from the tty.
+struct InputReader<R> {
+    source: R,
+    buf: VecDeque<u8>,
+}
+
+impl<R: Read + AsRawFd> InputReader<R> {
+    /// Reads the bytes available in the source into the buffer, returns false at EOF.
+    fn fill(&mut self) -> Result<bool, io::Error> {
+        let mut chunk = [0u8; 64];
+        let n = self.source.read(&mut chunk)?;
+        self.buf.extend(&chunk[..n]);
+        Ok(n > 0)
+    }
+
+    /// Returns true if input is available within the timeout.
+    fn wait_for_input(&self, timeout: Duration) -> bool {
+        let mut fd = libc::pollfd {
+            fd: self.source.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: the pointer is to a single pollfd, valid for the duration of the call
+        unsafe { libc::poll(&mut fd, 1, timeout.as_millis() as libc::c_int) > 0 }
+    }
+
+    /// Returns the next event, `None` at EOF. Replies that cannot be parsed are skipped.
+    fn next_event(&mut self) -> Option<Result<Event, io::Error>> {
+        loop {
+            if self.buf.is_empty() {
+                match self.fill() {
+                    Ok(true) => {}
+                    Ok(false) => return None,
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+
+            // a lone escape byte (read on its own) is the escape key itself, unless the rest
+            // of a pending reply follows shortly
+            if self.buf.len() == 1 && self.buf[0] == b'\x1b' {
+                if PENDING_REPLIES.load(Ordering::Relaxed) > 0 && self.wait_for_input(REPLY_WAIT) {
+                    match self.fill() {
+                        Ok(true) => continue,
+                        Ok(false) => {}
+                        Err(e) => return Some(Err(e)),
+                    }
+                }
+                self.buf.clear();
+                return Some(Ok(Event::Key(Key::Esc)));
+            }
+
+            // OSC and DCS sequences are replies only while some are pending, otherwise (or if
+            // the rest does not follow shortly) they are Alt-] and Alt-P
+            if self.buf.len() >= 2
+                && self.buf[0] == b'\x1b'
+                && matches!(self.buf[1], b']' | b'P')
+                && PENDING_REPLIES.load(Ordering::Relaxed) > 0
+            {
+                let osc = self.buf[1] == b']';
+                match self.read_string_sequence() {
+                    Ok(Some(reply)) => {
+                        let _ = PENDING_REPLIES.fetch_update(
+                            Ordering::Relaxed,
+                            Ordering::Relaxed,
+                            |n| n.checked_sub(1),
+                        );
+                        let event = if osc {
+                            Background::from_osc_reply(&reply).map(Event::Background)
+                        } else {
+                            CursorShape::from_dcs_reply(&reply).map(Event::CursorShape)
+                        };
+                        match event {
+                            Some(event) => return Some(Ok(event)),
+                            None => continue,
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+
+            let first = self.buf.pop_front()?;
+            let (buf, source) = (&mut self.buf, &mut self.source);
+            let mut rest = iter::from_fn(|| {
+                buf.pop_front().map(Ok).or_else(|| {
+                    let mut byte = [0u8];
+                    source
+                        .read_exact(&mut byte)
+                        .map(|_| byte[0])
+                        .map_or(None, |b| Some(Ok(b)))
+                })
+            });
+            match term_event::parse_event(first, &mut rest) {
+                Ok(term_event::Event::Key(key)) => return Some(Ok(Event::Key(key))),
+                Ok(_) | Err(_) => continue,
+            }
+        }
+    }
+
+    /// Consumes an OSC or DCS sequence (terminated by BEL or ST) from the buffer, returns its
+    /// content without the prefix and terminator, `None` (leaving the buffer as is) if the
+    /// terminator is not read within `REPLY_WAIT` or before EOF.
+    fn read_string_sequence(&mut self) -> Result<Option<String>, io::Error> {
+        loop {
+            let bytes = self.buf.make_contiguous();
+            let terminator = bytes
+                .iter()
+                .enumerate()
+                .skip(2)
+                .find_map(|(i, &b)| match b {
+                    b'\x07' => Some((i, 1)),
+                    b'\x1b' if bytes.get(i + 1) == Some(&b'\\') => Some((i, 2)),
+                    _ => None,
+                });
+            if let Some((end, len)) = terminator {
+                let reply = String::from_utf8_lossy(&bytes[2..end]).into_owned();
+                self.buf.drain(..end + len);
+                return Ok(Some(reply));
+            }
+            if !self.wait_for_input(REPLY_WAIT) || !self.fill()? {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+/// Spawns threads reading input from the tty, listening for signals and reading the lines
+/// written to the append FIFO (if any), sending the events from all to the channel.
+pub fn listen(tx: EventSender, append_fifo: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let mut reader = InputReader {
+        source: termion::get_tty()?,
+        buf: VecDeque::new(),
+    };
+    let input_tx = tx.clone();
+    thread::spawn(move || {
+        while let Some(event) = reader.next_event() {
+            if input_tx.send(event).is_err() {
+                break;
+            }
+        }
+    });
+
+    if let Some(path) = append_fifo {
+        let path = path.to_string();
+        let fifo_tx = tx.clone();
+        thread::spawn(move || {
+            // opening blocks until a writer opens the FIFO, and reading ends when it closes it
+            while let Ok(fifo) = File::open(&path) {
+                for line in BufReader::new(fifo).lines().map_while(Result::ok) {
+                    if fifo_tx.send(Ok(Event::Append(line))).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+    }
+
+    let mut signals = Signals::new([SIGUSR1, SIGWINCH])?;
+    thread::spawn(move || {
+        for signal in signals.forever() {
+            let event = if signal == SIGWINCH {
+                Event::Resize
+            } else {
+                Event::Reload
+            };
+            if tx.send(Ok(event)).is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Events read from a recording, along with the time they were received at.
+pub type Recording = Vec<(Duration, Event)>;
+
+/// Returns the events recorded to the file at the provided path.
+pub fn read_recording(path: &str) -> Result<Recording, Box<dyn Error>> {
+    let mut events = Vec::new();
+    for (num, line) in fs::read_to_string(path)?.lines().enumerate() {
+        let event = line.split_once(' ').and_then(|(ms, record)| {
+            Some((
+                Duration::from_millis(ms.parse().ok()?),
+                Event::from_record(record)?,
+            ))
+        });
+        events.push(event.ok_or(format!("invalid event at line {}", num + 1))?);
+    }
+    Ok(events)
+}
+
+/// Spawns a thread sending the recorded events to the channel with their original timing,
+/// followed by `Event::End`.
+pub fn replay(tx: EventSender, recording: Recording) {
+    thread::spawn(move || {
+        let start = Instant::now();
+        for (at, event) in recording {
+            thread::sleep(at.saturating_sub(start.elapsed()));
+            if tx.send(Ok(event)).is_err() {
+                return;
+            }
+        }
+        let _ = tx.send(Ok(Event::End));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keymap::{Action, Keymap, Profile};
+    use std::os::unix::net::UnixStream;
+
+    /// Returns a reader over the provided input, ending at EOF after it.
+    fn reader(input: &[u8]) -> InputReader<UnixStream> {
+        let (source, mut tty) = UnixStream::pair().unwrap();
+        tty.write_all(input).unwrap();
+        InputReader {
+            source,
+            buf: VecDeque::new(),
+        }
+    }
+
+    #[test]
+    fn string_sequence_prefix_is_a_key_without_pending_reply() {
+        let mut reader = reader(b"\x1bP");
+        match reader.next_event() {
+            Some(Ok(Event::Key(key))) => assert_eq!(key, Key::Alt('P')),
+            _ => panic!("expected a key"),
+        }
+    }
+    #[test]
+    fn unterminated_reply_is_a_key_after_the_wait() {
+        expect_reply();
+        let (source, mut tty) = UnixStream::pair().unwrap();
+        tty.write_all(b"\x1b]").unwrap();
+        let mut reader = InputReader {
+            source,
+            buf: VecDeque::new(),
+        };
+        // the tty stays open, so only the wait ends the sequence
+        match reader.next_event() {
+            Some(Ok(Event::Key(key))) => assert_eq!(key, Key::Alt(']')),
+            _ => panic!("expected a key"),
+        }
+    }
+    #[test]
+    fn emacs_move_entry_keys_reach_their_actions() {
+        let keymap = Keymap::for_profile(Profile::Emacs);
+        let mut reader = reader(b"\x1bP\x1bN");
+        for expected in [Action::MoveEntryUp, Action::MoveEntryDown] {
+            match reader.next_event() {
+                Some(Ok(Event::Key(key))) => assert!(keymap.action(key) == Some(expected)),
+                _ => panic!("expected a key"),
+            }
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use crate::delimiter::Delimiter;
+
+/// List of 1-based field ranges, parsed from a cut-style spec such as "1,3-4,6-".
+#[derive(Clone)]
+pub struct FieldList(Vec<(usize, Option<usize>)>);
+
+impl FieldList {
+    /// Returns the fields of the provided line included in the list, joined by the separator
+    /// found in the line.
+    pub fn extract(&self, line: &str, delimiter: &Delimiter) -> String {
+        let fields = delimiter.split(line);
+        let mut selected = Vec::new();
+        for &(start, end) in &self.0 {
+            let end = end.unwrap_or(fields.len()).min(fields.len());
+            for field in fields.iter().take(end).skip(start - 1) {
+                selected.push(*field);
+            }
+        }
+        selected.join(delimiter.separator(line))
+    }
+
+    /// Returns the 0-based indices covered by the list, for a sequence of the provided length.
+    pub fn indices(&self, len: usize) -> Vec<usize> {
+        let mut indices = Vec::new();
+        for &(start, end) in &self.0 {
+            let end = end.unwrap_or(len).min(len);
+            for i in start - 1..end {
+                if !indices.contains(&i) {
+                    indices.push(i);
+                }
+            }
+        }
+        indices
+    }
+}
+
+/// Parses a comma separated list of field numbers and ranges ("N", "N-M" or "N-").
+pub fn parse_field_list(spec: &str) -> Result<FieldList, String> {
+    let parse_num = |n: &str| match n.trim().parse::<usize>() {
+        Ok(0) | Err(_) => Err(format!("invalid number '{n}', numbering starts at 1")),
+        Ok(n) => Ok(n),
+    };
+
+    let mut ranges = Vec::new();
+    for part in spec.split(',') {
+        let range = match part.split_once('-') {
+            Some((start, "")) => (parse_num(start)?, None),
+            Some((start, end)) => (parse_num(start)?, Some(parse_num(end)?)),
+            None => {
+                let n = parse_num(part)?;
+                (n, Some(n))
+            }
+        };
+        ranges.push(range);
+    }
+    Ok(FieldList(ranges))
+}
@@ -0,0 +1,56 @@
+use std::iter;
+
+/// Section headers splitting the input lines into groups, given by lines starting with a
+/// marker (e.g., "--group:"), which are not entries themselves.
+pub struct Groups {
+    titles: Vec<String>,
+    /// Group of each line, `None` for lines before the first header.
+    groups: Vec<Option<usize>>,
+}
+
+impl Groups {
+    /// Removes the header lines (starting with the marker) from the lines, returning the
+    /// remaining ones and the groups they belong to, titled by the header text after the
+    /// marker.
+    pub fn split(lines: Vec<String>, marker: &str) -> (Vec<String>, Groups) {
+        let mut entries = Vec::with_capacity(lines.len());
+        let mut groups = Groups {
+            titles: Vec::new(),
+            groups: Vec::with_capacity(lines.len()),
+        };
+        for line in lines {
+            match line.strip_prefix(marker) {
+                Some(title) => groups.titles.push(title.trim().to_string()),
+                None => {
+                    groups.groups.push(groups.titles.len().checked_sub(1));
+                    entries.push(line);
+                }
+            }
+        }
+        (entries, groups)
+    }
+
+    /// Returns the groups of the lines read from several inputs one after the other, titled
+    /// by the names of the inputs, leaving out the first lines (pinned as header lines).
+    pub fn from_inputs(inputs: &[(String, usize)], skip: usize) -> Groups {
+        Groups {
+            titles: inputs.iter().map(|(name, _)| name.clone()).collect(),
+            groups: inputs
+                .iter()
+                .enumerate()
+                .flat_map(|(group, &(_, len))| iter::repeat_n(Some(group), len))
+                .skip(skip)
+                .collect(),
+        }
+    }
+
+    /// Returns the group of the line at the provided index.
+    pub fn group(&self, idx: usize) -> Option<usize> {
+        self.groups.get(idx).copied().flatten()
+    }
+
+    /// Returns the title of the group.
+    pub fn title(&self, group: usize) -> &str {
+        &self.titles[group]
+    }
+}
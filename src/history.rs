@@ -0,0 +1,92 @@
+use std::env;
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+
+use crate::command::shell_quote;
+
+/// Separator between the working directory and arguments of an invocation in the history
+/// file, which has one invocation per line.
+const FIELD_SEPARATOR: char = '\x1f';
+
+/// Working directory and command line arguments of a past invocation.
+#[derive(PartialEq)]
+pub struct Invocation {
+    pub dir: String,
+    pub args: Vec<String>,
+}
+
+impl Invocation {
+    /// Returns the arguments as typed in a shell (quoting those that need it), followed by
+    /// the directory they were run in.
+    pub fn display(&self) -> String {
+        let words: Vec<String> = self
+            .args
+            .iter()
+            .map(|a| {
+                if !a.is_empty()
+                    && a.chars()
+                        .all(|c| c.is_alphanumeric() || "@-_=./:,".contains(c))
+                {
+                    a.clone()
+                } else {
+                    shell_quote(a)
+                }
+            })
+            .collect();
+        format!("tui_selector {}  ({})", words.join(" "), self.dir)
+    }
+}
+
+/// Returns the path of the history file: "tui_selector/history" in `$XDG_STATE_HOME`
+/// (`~/.local/state` if unset).
+fn history_path() -> Option<PathBuf> {
+    let state_home = env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("state"))
+        })?;
+    Some(state_home.join("tui_selector").join("history"))
+}
+
+/// Appends the invocation to the history file.
+pub fn record(invocation: &Invocation) -> Result<(), Box<dyn Error>> {
+    let mut fields = vec![invocation.dir.as_str()];
+    fields.extend(invocation.args.iter().map(String::as_str));
+    if fields.iter().any(|f| f.contains(['\n', FIELD_SEPARATOR])) {
+        return Ok(());
+    }
+    let path = history_path().ok_or("unknown home directory")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", fields.join(&FIELD_SEPARATOR.to_string()))?;
+    Ok(())
+}
+
+/// Returns the recorded invocations, most recent first and without repetitions.
+pub fn recent() -> Result<Vec<Invocation>, Box<dyn Error>> {
+    let path = history_path().ok_or("unknown home directory")?;
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let mut invocations: Vec<Invocation> = Vec::new();
+    for line in content.lines().rev() {
+        let mut fields = line.split(FIELD_SEPARATOR).map(String::from);
+        let Some(dir) = fields.next() else {
+            continue;
+        };
+        let invocation = Invocation {
+            dir,
+            args: fields.collect(),
+        };
+        if !invocations.contains(&invocation) {
+            invocations.push(invocation);
+        }
+    }
+    Ok(invocations)
+}
@@ -0,0 +1,331 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufWriter, Write};
+use std::rc::Rc;
+use std::time::UNIX_EPOCH;
+
+use memmap2::Mmap;
+use regex::Regex;
+
+use crate::ansi;
+
+/// Read-only list of input lines.
+pub trait Items {
+    /// Returns the amount of lines.
+    fn len(&self) -> usize;
+    /// Returns the line at the provided index, which must be lower than `len()`.
+    fn get(&self, idx: usize) -> Cow<'_, str>;
+}
+
+impl Items for Vec<String> {
+    fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    fn get(&self, idx: usize) -> Cow<'_, str> {
+        Cow::Borrowed(&self[idx])
+    }
+}
+
+/// Lines of a memory-mapped file, only the offsets where each line starts are kept in memory
+/// and lines are decoded when accessed.
+pub struct MappedFile {
+    map: Mmap,
+    line_starts: Vec<usize>,
+}
+
+impl MappedFile {
+    /// Memory-map the file at the provided path and index its line offsets. With `cache_index`
+    /// the index is read from (or written to) a file next to it, valid while the file's
+    /// modification time and size are unchanged.
+    pub fn open(path: &str, cache_index: bool) -> Result<MappedFile, Box<dyn Error>> {
+        let file = File::open(path)?;
+        // SAFETY: the map is read-only, modifying the file while it is mapped only affects
+        // the displayed content
+        let map = unsafe { Mmap::map(&file)? };
+
+        if !cache_index {
+            let line_starts = index_lines(&map);
+            return Ok(MappedFile { map, line_starts });
+        }
+
+        let cache_path = format!("{path}{INDEX_CACHE_EXT}");
+        let stamp = file_stamp(&file.metadata()?)?;
+        let line_starts = match read_index_cache(&cache_path, &stamp, map.len()) {
+            Some(line_starts) => line_starts,
+            None => {
+                let line_starts = index_lines(&map);
+                // failing to write the cache (e.g., read-only directory) only costs the speedup
+                let _ = write_index_cache(&cache_path, &stamp, &line_starts);
+                line_starts
+            }
+        };
+        Ok(MappedFile { map, line_starts })
+    }
+}
+
+/// Extension appended to a file's path for its line index cache.
+const INDEX_CACHE_EXT: &str = ".tsidx";
+
+/// Magic bytes at the start of line index cache files, followed by the file stamp and the
+/// amount of lines.
+const INDEX_CACHE_MAGIC: &[u8; 8] = b"TSIDX002";
+
+/// Returns the offsets where each line of the provided content starts.
+fn index_lines(content: &[u8]) -> Vec<usize> {
+    let mut line_starts = vec![0];
+    line_starts.extend(memchr::memchr_iter(b'\n', content).map(|i| i + 1));
+    if line_starts.last() == Some(&content.len()) {
+        line_starts.pop();
+    }
+    line_starts
+}
+
+/// Returns bytes identifying the file version: its modification time and size.
+fn file_stamp(metadata: &Metadata) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mtime = metadata.modified()?.duration_since(UNIX_EPOCH)?;
+    let mut stamp = Vec::new();
+    stamp.extend(mtime.as_secs().to_le_bytes());
+    stamp.extend(mtime.subsec_nanos().to_le_bytes());
+    stamp.extend(metadata.len().to_le_bytes());
+    Ok(stamp)
+}
+
+/// Returns the cached line index for content of the provided length, `None` if the cache is
+/// missing, outdated or corrupt.
+fn read_index_cache(cache_path: &str, stamp: &[u8], content_len: usize) -> Option<Vec<usize>> {
+    let cache = fs::read(cache_path).ok()?;
+    let rest = cache.strip_prefix(INDEX_CACHE_MAGIC)?.strip_prefix(stamp)?;
+    let (count, offsets) = rest.split_first_chunk::<8>()?;
+    if offsets.len() % 8 != 0 || offsets.len() / 8 != u64::from_le_bytes(*count) as usize {
+        return None;
+    }
+    let line_starts: Vec<usize> = offsets
+        .chunks_exact(8)
+        .map(|c| u64::from_le_bytes(c.try_into().unwrap_or_default()) as usize)
+        .collect();
+    // each line but the last ends with a newline, so lines start strictly after each other
+    let valid = line_starts.first().is_none_or(|&first| first == 0)
+        && line_starts.windows(2).all(|w| w[0] < w[1])
+        && line_starts.last().is_none_or(|&last| last < content_len);
+    valid.then_some(line_starts)
+}
+
+/// Writes the line index to the cache file, along with the file stamp it is valid for.
+fn write_index_cache(cache_path: &str, stamp: &[u8], line_starts: &[usize]) -> io::Result<()> {
+    let mut cache = BufWriter::new(File::create(cache_path)?);
+    cache.write_all(INDEX_CACHE_MAGIC)?;
+    cache.write_all(stamp)?;
+    cache.write_all(&(line_starts.len() as u64).to_le_bytes())?;
+    for &offset in line_starts {
+        cache.write_all(&(offset as u64).to_le_bytes())?;
+    }
+    cache.flush()
+}
+
+impl Items for MappedFile {
+    fn len(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    fn get(&self, idx: usize) -> Cow<'_, str> {
+        let start = self.line_starts[idx];
+        let end = self
+            .line_starts
+            .get(idx + 1)
+            .map_or(self.map.len(), |&e| e - 1);
+        match String::from_utf8_lossy(&self.map[start..end]) {
+            Cow::Borrowed(line) => Cow::Borrowed(line.trim()),
+            Cow::Owned(line) => Cow::Owned(line.trim().to_string()),
+        }
+    }
+}
+
+/// Lines of another list without their escape sequences, for input colored with ANSI codes.
+pub struct AnsiStripped(pub Rc<dyn Items>);
+
+impl Items for AnsiStripped {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn get(&self, idx: usize) -> Cow<'_, str> {
+        match self.0.get(idx) {
+            Cow::Borrowed(line) if !line.contains('\x1b') => Cow::Borrowed(line),
+            line => Cow::Owned(ansi::strip(&line)),
+        }
+    }
+}
+
+/// Lines of another list without its first lines, which are header lines.
+pub struct Skipped(pub Rc<dyn Items>, pub usize);
+
+impl Items for Skipped {
+    fn len(&self) -> usize {
+        self.0.len().saturating_sub(self.1)
+    }
+
+    fn get(&self, idx: usize) -> Cow<'_, str> {
+        self.0.get(idx + self.1)
+    }
+}
+
+/// Lines of another list followed by the lines appended to it while the selector is running.
+pub struct Appended {
+    base: Rc<dyn Items>,
+    lines: RefCell<Vec<String>>,
+}
+
+impl Appended {
+    /// Create new `Appended` list, initially with the lines of the provided one.
+    pub fn new(base: Rc<dyn Items>) -> Appended {
+        Appended {
+            base,
+            lines: RefCell::new(Vec::new()),
+        }
+    }
+}
+
+impl Items for Appended {
+    fn len(&self) -> usize {
+        self.base.len() + self.lines.borrow().len()
+    }
+
+    fn get(&self, idx: usize) -> Cow<'_, str> {
+        match idx.checked_sub(self.base.len()) {
+            Some(appended) => Cow::Owned(self.lines.borrow()[appended].clone()),
+            None => self.base.get(idx),
+        }
+    }
+}
+
+/// Function returning the ID of a line, by which appended lines are deduplicated.
+pub type IdFn = Box<dyn Fn(&str) -> String>;
+
+/// Appends lines to a list while it is displayed, skipping those whose ID is already listed.
+pub struct Appender {
+    items: Rc<Appended>,
+    id: IdFn,
+    ids: HashSet<String>,
+    /// Pattern of the appended lines shown as disabled entries.
+    disabled: Option<Regex>,
+}
+
+impl Appender {
+    /// Create new `Appender` adding lines to the provided list, whose entries are `listed`
+    /// (i.e., the lines as displayed, without header lines).
+    pub fn new(
+        items: Rc<Appended>,
+        listed: &dyn Items,
+        id: IdFn,
+        disabled: Option<Regex>,
+    ) -> Appender {
+        let ids = (0..listed.len()).map(|i| id(&listed.get(i))).collect();
+        Appender {
+            items,
+            id,
+            ids,
+            disabled,
+        }
+    }
+
+    /// Appends the line unless it is empty or its ID is already listed, returns whether it
+    /// was appended.
+    pub fn push(&mut self, line: &str) -> bool {
+        let line = line.trim();
+        if line.is_empty() || !self.ids.insert((self.id)(line)) {
+            return false;
+        }
+        self.items.lines.borrow_mut().push(line.to_string());
+        true
+    }
+
+    /// Returns true if the appended line is shown as a disabled entry.
+    pub fn is_disabled(&self, line: &str) -> bool {
+        self.disabled
+            .as_ref()
+            .is_some_and(|pattern| pattern.is_match(line))
+    }
+}
+
+/// Function formatting the line at the provided index for display.
+pub type EntryFormat = Box<dyn Fn(usize, &str) -> String>;
+
+/// Function returning the text printed when running the selection of the entries at the
+/// provided indices.
+pub type OutputFn = Box<dyn Fn(&[usize]) -> String>;
+
+/// Entries shown in the selector, formatted for display from the input lines when accessed.
+pub struct Entries {
+    pub items: Rc<dyn Items>,
+    pub format: EntryFormat,
+    /// Input lines with their colors, displayed instead of `items` when set.
+    pub colored: Option<Rc<dyn Items>>,
+    /// Remove escape sequences and make control characters visible when displaying entries.
+    pub sanitize: bool,
+}
+
+impl Entries {
+    /// Returns the amount of entries.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns the entry at the provided index as displayed in the selector.
+    pub fn display(&self, idx: usize) -> String {
+        let entry = (self.format)(idx, &self.items.get(idx));
+        if self.sanitize {
+            ansi::sanitize(&entry)
+        } else {
+            entry
+        }
+    }
+
+    /// Returns the entry at the provided index with the colors of its input line, `None`
+    /// without colored input or when formatting the colored line gives a different text
+    /// (e.g., fields split within an escape sequence).
+    pub fn display_colored(&self, idx: usize) -> Option<String> {
+        let colored = (self.format)(idx, &self.colored.as_ref()?.get(idx));
+        let mut colored = ansi::keep_sgr(&colored);
+        if self.sanitize {
+            colored = ansi::visualize_controls(&colored);
+        }
+        (ansi::strip(&colored) == self.display(idx)).then_some(colored)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corrupt_index_cache_is_rebuilt() {
+        let path = std::env::temp_dir().join(format!("tsidx-test-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        fs::write(path, "one\ntwo\nthree\n").unwrap();
+        let stamp = file_stamp(&fs::metadata(path).unwrap()).unwrap();
+        let cache_path = format!("{path}{INDEX_CACHE_EXT}");
+
+        // decreasing, out of range, not starting at 0, and with a wrong line count
+        for line_starts in [vec![0, 8, 4], vec![0, 4, 80], vec![4, 8], vec![0, 4, 8]] {
+            let mut cache = INDEX_CACHE_MAGIC.to_vec();
+            cache.extend(&stamp);
+            let count = if line_starts == [0, 4, 8] { 2 } else { 3 };
+            cache.extend((count as u64).to_le_bytes());
+            for offset in &line_starts {
+                cache.extend((*offset as u64).to_le_bytes());
+            }
+            fs::write(&cache_path, cache).unwrap();
+
+            let file = MappedFile::open(path, true).unwrap();
+            let lines: Vec<_> = (0..file.len()).map(|i| file.get(i)).collect();
+            assert_eq!(lines, ["one", "two", "three"]);
+        }
+
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(&cache_path);
+    }
+}
@@ -0,0 +1,536 @@
+use termion::event::Key;
+
+/// Set of default keybindings.
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum Profile {
+    /// Vim-like movement, space/l/right selects and q/esc quits
+    Default,
+    /// j/k to move, space to select, q/esc to quit
+    Vim,
+    /// ctrl-n/ctrl-p to move, tab to select, ctrl-g/esc to quit
+    Emacs,
+    /// Arrow keys to move, space to select, esc to quit
+    ArrowsOnly,
+}
+
+/// Format of the keybindings cheat sheet.
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum SheetFormat {
+    /// Markdown table, to embed in documentation
+    Markdown,
+    /// Plain text aligned in columns, to print
+    Table,
+}
+
+/// Operations that can be bound to keys.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Action {
+    Up,
+    Down,
+    Top,
+    Bottom,
+    Toggle,
+    SelectAll,
+    SelectNone,
+    SelectPattern,
+    Search,
+    SearchNext,
+    SearchPrev,
+    Filter,
+    CycleMatchMode,
+    PreviewUp,
+    PreviewDown,
+    /// Runs the preview command again instead of showing its cached output.
+    RefreshPreview,
+    Reshuffle,
+    /// Hides the children of the tree node, or moves to its parent.
+    Collapse,
+    /// Shows the children of the tree node.
+    Expand,
+    /// Waits for a character and moves to the next entry starting with it.
+    Jump,
+    ScrollLeft,
+    ScrollRight,
+    /// Waits for a letter and marks the current entry with it.
+    SetMark,
+    /// Waits for a letter and moves to the entry marked with it.
+    GoToMark,
+    /// Shows or hides the files ignored by git with `--glob`.
+    ToggleIgnored,
+    /// Shows or hides the hidden files with `--glob`.
+    ToggleHidden,
+    /// Labels the entries on screen and moves to the one whose label is typed next.
+    Hint,
+    /// Shows what running the selection would print, without running it.
+    ShowOutput,
+    /// Swaps the entry under the cursor with the one listed above it.
+    MoveEntryUp,
+    /// Swaps the entry under the cursor with the one listed below it.
+    MoveEntryDown,
+    /// Opens the menu of actions declared in the configuration file on the current entry.
+    Menu,
+    Accept,
+    Quit,
+    /// Custom action defined by the script, by position in its bindings.
+    #[cfg(feature = "lua")]
+    Script(usize),
+}
+
+impl Action {
+    /// Returns the name of the action as listed in the cheat sheet.
+    fn description(self) -> &'static str {
+        match self {
+            Action::Up => "Move up",
+            Action::Down => "Move down",
+            Action::Top => "First entry",
+            Action::Bottom => "Last entry",
+            Action::Toggle => "Select",
+            Action::SelectAll => "Select all",
+            Action::SelectNone => "Deselect all",
+            Action::SelectPattern => "Toggle matching",
+            Action::Search => "Search",
+            Action::SearchNext => "Next match",
+            Action::SearchPrev => "Previous match",
+            Action::Filter => "Filter",
+            Action::CycleMatchMode => "Cycle match mode",
+            Action::PreviewUp => "Scroll preview up",
+            Action::PreviewDown => "Scroll preview down",
+            Action::RefreshPreview => "Refresh preview",
+            Action::Reshuffle => "Reshuffle",
+            Action::Collapse => "Collapse node",
+            Action::Expand => "Expand node",
+            Action::Jump => "Jump to initial",
+            Action::ScrollLeft => "Scroll left",
+            Action::ScrollRight => "Scroll right",
+            Action::SetMark => "Set mark",
+            Action::GoToMark => "Go to mark",
+            Action::ToggleIgnored => "Toggle ignored files",
+            Action::ToggleHidden => "Toggle hidden files",
+            Action::Hint => "Go to label",
+            Action::ShowOutput => "Show output",
+            Action::MoveEntryUp => "Move entry up",
+            Action::MoveEntryDown => "Move entry down",
+            Action::Menu => "Open action menu",
+            Action::Accept => "Run selection",
+            Action::Quit => "Quit",
+            #[cfg(feature = "lua")]
+            Action::Script(_) => "Script action",
+        }
+    }
+
+    /// Returns the short description of the action shown in the header hint, `None` for
+    /// actions not shown there.
+    fn hint_label(self) -> Option<&'static str> {
+        match self {
+            Action::Toggle => Some("select"),
+            Action::Accept => Some("run selection"),
+            Action::Quit => Some("quit"),
+            Action::SelectAll => Some("select all"),
+            Action::SelectNone => Some("deselect all"),
+            _ => None,
+        }
+    }
+}
+
+/// Names of the actions in `--bind`, as in fzf where it has the same action.
+const ACTION_NAMES: [(&str, Action); 33] = [
+    ("up", Action::Up),
+    ("down", Action::Down),
+    ("first", Action::Top),
+    ("last", Action::Bottom),
+    ("toggle", Action::Toggle),
+    ("select-all", Action::SelectAll),
+    ("deselect-all", Action::SelectNone),
+    ("toggle-matching", Action::SelectPattern),
+    ("search", Action::Search),
+    ("next-match", Action::SearchNext),
+    ("previous-match", Action::SearchPrev),
+    ("filter", Action::Filter),
+    ("cycle-match-mode", Action::CycleMatchMode),
+    ("preview-up", Action::PreviewUp),
+    ("preview-down", Action::PreviewDown),
+    ("refresh-preview", Action::RefreshPreview),
+    ("reshuffle", Action::Reshuffle),
+    ("collapse", Action::Collapse),
+    ("expand", Action::Expand),
+    ("jump", Action::Jump),
+    ("scroll-left", Action::ScrollLeft),
+    ("scroll-right", Action::ScrollRight),
+    ("set-mark", Action::SetMark),
+    ("go-to-mark", Action::GoToMark),
+    ("toggle-ignored", Action::ToggleIgnored),
+    ("toggle-hidden", Action::ToggleHidden),
+    ("go-to-label", Action::Hint),
+    ("show-output", Action::ShowOutput),
+    ("move-entry-up", Action::MoveEntryUp),
+    ("move-entry-down", Action::MoveEntryDown),
+    ("menu", Action::Menu),
+    ("accept", Action::Accept),
+    ("abort", Action::Quit),
+];
+
+/// Key bound with `--bind` to an action, or to none to leave it unbound.
+#[derive(Clone)]
+pub struct Binding {
+    key: Key,
+    action: Option<Action>,
+}
+
+/// Parses the binding from its "KEY:ACTION" argument, the key being named as in the header
+/// hint and the action as in `ACTION_NAMES`, or "ignore" to unbind the key.
+pub fn parse_binding(arg: &str) -> Result<Binding, String> {
+    let (key_name, action_name) = arg
+        .rsplit_once(':')
+        .ok_or(format!("invalid binding '{arg}', expected KEY:ACTION"))?;
+    let key = parse_key(key_name).ok_or(format!("unknown key '{key_name}'"))?;
+    let action = match action_name {
+        "ignore" => None,
+        _ => Some(parse_action(action_name).ok_or(format!("unknown action '{action_name}'"))?),
+    };
+    Ok(Binding { key, action })
+}
+
+/// Returns the action with the provided name, as in `ACTION_NAMES`.
+pub fn parse_action(name: &str) -> Option<Action> {
+    ACTION_NAMES
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|&(_, action)| action)
+}
+
+/// Table binding keys to the actions they trigger.
+pub struct Keymap {
+    bindings: Vec<(Key, Action)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        Keymap::for_profile(Profile::Default)
+    }
+}
+
+impl Keymap {
+    /// Returns the keymap with the bindings of the provided profile.
+    pub fn for_profile(profile: Profile) -> Keymap {
+        use Action::*;
+        let bindings = match profile {
+            Profile::Default => vec![
+                (Key::Up, Up),
+                (Key::Char('k'), Up),
+                (Key::Down, Down),
+                (Key::Char('j'), Down),
+                (Key::Char(' '), Toggle),
+                (Key::Char('l'), Toggle),
+                (Key::Right, Toggle),
+                (Key::Char('\n'), Accept),
+                (Key::Char('q'), Quit),
+                (Key::Esc, Quit),
+                (Key::Char('a'), SelectAll),
+                (Key::Char('u'), SelectNone),
+                (Key::Char(':'), SelectPattern),
+                (Key::Char('/'), Search),
+                (Key::Char('n'), SearchNext),
+                (Key::Char('N'), SearchPrev),
+                (Key::Char('&'), Filter),
+                (Key::Char('M'), CycleMatchMode),
+                (Key::Ctrl('y'), PreviewUp),
+                (Key::Ctrl('e'), PreviewDown),
+                (Key::Ctrl('r'), RefreshPreview),
+                (Key::Char('R'), Reshuffle),
+                (Key::Char('-'), Collapse),
+                (Key::Char('+'), Expand),
+                (Key::Char('\''), Jump),
+                (Key::Char('H'), ScrollLeft),
+                (Key::Char('L'), ScrollRight),
+                (Key::Char('m'), SetMark),
+                (Key::Char('`'), GoToMark),
+                (Key::Char('I'), ToggleIgnored),
+                (Key::Char('.'), ToggleHidden),
+                (Key::Char('f'), Hint),
+                (Key::Char('o'), ShowOutput),
+                (Key::Char('K'), MoveEntryUp),
+                (Key::Char('J'), MoveEntryDown),
+                (Key::Char('x'), Menu),
+            ],
+            Profile::Vim => vec![
+                (Key::Char('k'), Up),
+                (Key::Up, Up),
+                (Key::Char('j'), Down),
+                (Key::Down, Down),
+                (Key::Char('g'), Top),
+                (Key::Char('G'), Bottom),
+                (Key::Char(' '), Toggle),
+                (Key::Char('\n'), Accept),
+                (Key::Char('q'), Quit),
+                (Key::Esc, Quit),
+                (Key::Char('a'), SelectAll),
+                (Key::Char('u'), SelectNone),
+                (Key::Char(':'), SelectPattern),
+                (Key::Char('/'), Search),
+                (Key::Char('n'), SearchNext),
+                (Key::Char('N'), SearchPrev),
+                (Key::Char('&'), Filter),
+                (Key::Char('M'), CycleMatchMode),
+                (Key::Ctrl('y'), PreviewUp),
+                (Key::Ctrl('e'), PreviewDown),
+                (Key::Ctrl('r'), RefreshPreview),
+                (Key::Char('R'), Reshuffle),
+                (Key::Char('h'), Collapse),
+                (Key::Char('-'), Collapse),
+                (Key::Char('l'), Expand),
+                (Key::Char('+'), Expand),
+                (Key::Char('\''), Jump),
+                (Key::Char('H'), ScrollLeft),
+                (Key::Char('L'), ScrollRight),
+                (Key::Char('m'), SetMark),
+                (Key::Char('`'), GoToMark),
+                (Key::Char('I'), ToggleIgnored),
+                (Key::Char('.'), ToggleHidden),
+                (Key::Char('f'), Hint),
+                (Key::Char('o'), ShowOutput),
+                (Key::Char('K'), MoveEntryUp),
+                (Key::Char('J'), MoveEntryDown),
+                (Key::Char('x'), Menu),
+            ],
+            Profile::Emacs => vec![
+                (Key::Ctrl('p'), Up),
+                (Key::Up, Up),
+                (Key::Ctrl('n'), Down),
+                (Key::Down, Down),
+                (Key::Alt('<'), Top),
+                (Key::Alt('>'), Bottom),
+                (Key::Char('\t'), Toggle),
+                (Key::Null, Toggle),
+                (Key::Char('\n'), Accept),
+                (Key::Ctrl('g'), Quit),
+                (Key::Esc, Quit),
+                (Key::Alt('a'), SelectAll),
+                (Key::Alt('u'), SelectNone),
+                (Key::Alt('m'), SelectPattern),
+                (Key::Ctrl('s'), Search),
+                (Key::Alt('s'), SearchNext),
+                (Key::Alt('r'), SearchPrev),
+                (Key::Alt('f'), Filter),
+                (Key::Alt('M'), CycleMatchMode),
+                (Key::Alt('p'), PreviewUp),
+                (Key::Alt('n'), PreviewDown),
+                (Key::Ctrl('l'), RefreshPreview),
+                (Key::Alt('R'), Reshuffle),
+                (Key::Char('-'), Collapse),
+                (Key::Char('+'), Expand),
+                (Key::Alt('j'), Jump),
+                (Key::Alt('h'), ScrollLeft),
+                (Key::Alt('l'), ScrollRight),
+                (Key::Alt('k'), SetMark),
+                (Key::Alt('`'), GoToMark),
+                (Key::Alt('i'), ToggleIgnored),
+                (Key::Alt('.'), ToggleHidden),
+                (Key::Alt('g'), Hint),
+                (Key::Alt('o'), ShowOutput),
+                (Key::Alt('P'), MoveEntryUp),
+                (Key::Alt('N'), MoveEntryDown),
+                (Key::Alt('x'), Menu),
+            ],
+            Profile::ArrowsOnly => vec![
+                (Key::Up, Up),
+                (Key::Down, Down),
+                (Key::Home, Top),
+                (Key::End, Bottom),
+                (Key::Char(' '), Toggle),
+                (Key::Char('\n'), Accept),
+                (Key::Esc, Quit),
+                (Key::Ctrl('a'), SelectAll),
+                (Key::Ctrl('d'), SelectNone),
+                (Key::Ctrl('t'), SelectPattern),
+                (Key::Ctrl('f'), Search),
+                (Key::F(3), SearchNext),
+                (Key::F(4), SearchPrev),
+                (Key::F(2), Filter),
+                (Key::F(5), CycleMatchMode),
+                (Key::PageUp, PreviewUp),
+                (Key::PageDown, PreviewDown),
+                (Key::Ctrl('r'), RefreshPreview),
+                (Key::F(6), Reshuffle),
+                (Key::Char('-'), Collapse),
+                (Key::Char('+'), Expand),
+                (Key::F(7), Jump),
+                (Key::Left, ScrollLeft),
+                (Key::Right, ScrollRight),
+                (Key::F(8), SetMark),
+                (Key::F(9), GoToMark),
+                (Key::F(10), ToggleIgnored),
+                (Key::F(11), ToggleHidden),
+                (Key::F(12), Hint),
+                (Key::Ctrl('o'), ShowOutput),
+                (Key::Ctrl('p'), MoveEntryUp),
+                (Key::Ctrl('n'), MoveEntryDown),
+                (Key::Ctrl('x'), Menu),
+            ],
+        };
+        Keymap { bindings }
+    }
+
+    /// Applies the bindings set by the user over those of the profile.
+    pub fn apply(&mut self, bindings: &[Binding]) {
+        for binding in bindings {
+            match binding.action {
+                Some(action) => self.bind(binding.key, action),
+                None => self.bindings.retain(|(k, _)| *k != binding.key),
+            }
+        }
+    }
+
+    /// Binds the key to the action, replacing its previous binding.
+    pub fn bind(&mut self, key: Key, action: Action) {
+        self.bindings.retain(|(k, _)| *k != key);
+        self.bindings.push((key, action));
+    }
+
+    /// Removes the bindings of the provided actions.
+    pub fn unbind(&mut self, actions: &[Action]) {
+        self.bindings.retain(|(_, a)| !actions.contains(a));
+    }
+
+    /// Returns the action bound to the provided key.
+    pub fn action(&self, key: Key) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|&(_, a)| a)
+    }
+
+    /// Returns the items of the keybindings hint shown in the header, most important first,
+    /// e.g., "l/right:select" and "enter:run selection".
+    pub fn hint_items(&self) -> Vec<String> {
+        let mut hints: Vec<(Action, Vec<String>)> = Vec::new();
+        for &(key, action) in &self.bindings {
+            if action.hint_label().is_none() {
+                continue;
+            }
+            match hints.iter_mut().find(|(a, _)| *a == action) {
+                Some((_, keys)) => keys.push(key_name(key)),
+                None => hints.push((action, vec![key_name(key)])),
+            }
+        }
+        hints.sort_by_key(|(a, _)| HINT_ORDER.iter().position(|h| h == a));
+        hints
+            .iter()
+            .map(|(a, keys)| format!("{}:{}", keys.join("/"), a.hint_label().unwrap_or_default()))
+            .collect()
+    }
+
+    /// Returns the cheat sheet listing every action with the keys bound to it, in the
+    /// provided format.
+    pub fn cheat_sheet(&self, format: SheetFormat) -> String {
+        let mut actions: Vec<(Action, Vec<String>)> = Vec::new();
+        for &(key, action) in &self.bindings {
+            match actions.iter_mut().find(|(a, _)| *a == action) {
+                Some((_, keys)) => keys.push(key_name(key)),
+                None => actions.push((action, vec![key_name(key)])),
+            }
+        }
+        let mut sheet = String::new();
+        match format {
+            SheetFormat::Markdown => {
+                sheet.push_str("| Action | Keys |\n|--------|------|\n");
+                for (action, keys) in &actions {
+                    let keys: Vec<String> = keys.iter().map(|k| markdown_code(k)).collect();
+                    sheet.push_str(&format!(
+                        "| {} | {} |\n",
+                        action.description(),
+                        keys.join(", ")
+                    ));
+                }
+            }
+            SheetFormat::Table => {
+                let width = actions
+                    .iter()
+                    .map(|(a, _)| a.description().len())
+                    .max()
+                    .unwrap_or(0);
+                for (action, keys) in &actions {
+                    sheet.push_str(&format!(
+                        "{:width$}  {}\n",
+                        action.description(),
+                        keys.join(", ")
+                    ));
+                }
+            }
+        }
+        sheet
+    }
+}
+
+/// Returns the key name as Markdown inline code, fenced with double backticks if it contains
+/// one and with its pipes escaped so it fits in a table cell.
+fn markdown_code(name: &str) -> String {
+    let name = name.replace('|', "\\|");
+    if name.contains('`') {
+        format!("`` {name} ``")
+    } else {
+        format!("`{name}`")
+    }
+}
+
+/// Order of the actions in the header hint, by importance.
+const HINT_ORDER: [Action; 5] = [
+    Action::Toggle,
+    Action::Accept,
+    Action::Quit,
+    Action::SelectAll,
+    Action::SelectNone,
+];
+
+/// Returns the name of the key as written in hints, e.g., "ctrl-a" or "enter".
+pub fn key_name(key: Key) -> String {
+    match key {
+        Key::Char('\n') => String::from("enter"),
+        Key::Char('\t') => String::from("tab"),
+        Key::Char(' ') => String::from("space"),
+        Key::Char(c) => c.to_string(),
+        Key::Alt(c) => format!("alt-{c}"),
+        Key::Ctrl(c) => format!("ctrl-{c}"),
+        Key::Null => String::from("ctrl-space"),
+        Key::F(n) => format!("f{n}"),
+        other => format!("{other:?}").to_lowercase(),
+    }
+}
+
+/// Returns the key with the provided name as written in hints, e.g., "ctrl-a" or "enter".
+pub fn parse_key(name: &str) -> Option<Key> {
+    let single = |c: &str| {
+        let mut chars = c.chars();
+        chars.next().filter(|_| chars.next().is_none())
+    };
+    let key = match name {
+        "enter" => Key::Char('\n'),
+        "tab" => Key::Char('\t'),
+        "space" => Key::Char(' '),
+        "ctrl-space" => Key::Null,
+        "esc" => Key::Esc,
+        "backspace" => Key::Backspace,
+        "backtab" => Key::BackTab,
+        "insert" => Key::Insert,
+        "delete" => Key::Delete,
+        "up" => Key::Up,
+        "down" => Key::Down,
+        "left" => Key::Left,
+        "right" => Key::Right,
+        "home" => Key::Home,
+        "end" => Key::End,
+        "pageup" => Key::PageUp,
+        "pagedown" => Key::PageDown,
+        _ => {
+            if let Some(c) = name.strip_prefix("ctrl-").and_then(single) {
+                Key::Ctrl(c)
+            } else if let Some(c) = name.strip_prefix("alt-").and_then(single) {
+                Key::Alt(c)
+            } else if let Some(n) = name.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                Key::F(n)
+            } else {
+                Key::Char(single(name)?)
+            }
+        }
+    };
+    Some(key)
+}
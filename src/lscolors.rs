@@ -0,0 +1,105 @@
+use std::cmp;
+use std::env;
+use std::fs::{self, Metadata};
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+/// Colors used when `$LS_COLORS` is not set, those of GNU ls by default.
+const DEFAULT_LS_COLORS: &str =
+    "di=01;34:ln=01;36:ex=01;32:pi=40;33:so=01;35:bd=40;33;01:cd=40;33;01:or=40;31;01";
+
+/// Colors of files by type and extension, as used by ls, read from `$LS_COLORS`.
+pub struct LsColors {
+    /// SGR parameters (e.g., "01;34") by file type code (e.g., "di" for directories).
+    types: Vec<(String, String)>,
+    /// SGR parameters by file name suffix (e.g., ".tar" for "*.tar"), in lowercase.
+    suffixes: Vec<(String, String)>,
+}
+
+impl LsColors {
+    /// Returns the colors set in `$LS_COLORS`, or those of GNU ls if unset.
+    pub fn from_env() -> LsColors {
+        let spec = env::var("LS_COLORS")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| DEFAULT_LS_COLORS.to_string());
+        LsColors::parse(&spec)
+    }
+
+    /// Parses the "KEY=SGR:..." format of `$LS_COLORS`, keys being file type codes or
+    /// "*SUFFIX" patterns. Entries not in this format are skipped.
+    fn parse(spec: &str) -> LsColors {
+        let mut colors = LsColors {
+            types: Vec::new(),
+            suffixes: Vec::new(),
+        };
+        for (key, sgr) in spec.split(':').filter_map(|entry| entry.split_once('=')) {
+            match key.strip_prefix('*') {
+                Some(suffix) => colors
+                    .suffixes
+                    .push((suffix.to_lowercase(), sgr.to_string())),
+                None => colors.types.push((key.to_string(), sgr.to_string())),
+            }
+        }
+        // the longest suffix wins, e.g., "*.tar.gz" over "*.gz"
+        colors
+            .suffixes
+            .sort_by_key(|(suffix, _)| cmp::Reverse(suffix.len()));
+        colors
+    }
+
+    /// Returns the SGR parameters of the file at the provided path, `None` if it does not
+    /// exist or is a regular file without a color.
+    pub fn style(&self, path: &str) -> Option<&str> {
+        let metadata = fs::symlink_metadata(path).ok()?;
+        let code = file_type_code(path, &metadata);
+        if let Some(style) = code.and_then(|code| self.type_style(code)) {
+            return Some(style);
+        }
+        if metadata.is_file() {
+            let name = path.to_lowercase();
+            let suffix = self
+                .suffixes
+                .iter()
+                .find(|(suffix, _)| name.ends_with(suffix.as_str()));
+            if let Some((_, style)) = suffix {
+                return Some(style);
+            }
+        }
+        self.type_style("fi")
+    }
+
+    /// Returns the SGR parameters of the file type code, `None` if unset or reset ("0").
+    fn type_style(&self, code: &str) -> Option<&str> {
+        let (_, style) = self.types.iter().rev().find(|(c, _)| c == code)?;
+        (style != "0" && style != "00").then_some(style.as_str())
+    }
+}
+
+/// Returns the `$LS_COLORS` code of the file type (e.g., "di"), `None` for regular files
+/// that are not executable.
+fn file_type_code(path: &str, metadata: &Metadata) -> Option<&'static str> {
+    let file_type = metadata.file_type();
+    if file_type.is_symlink() {
+        // links whose target is missing are orphans
+        return Some(if fs::metadata(path).is_ok() {
+            "ln"
+        } else {
+            "or"
+        });
+    }
+    if file_type.is_dir() {
+        Some("di")
+    } else if file_type.is_fifo() {
+        Some("pi")
+    } else if file_type.is_socket() {
+        Some("so")
+    } else if file_type.is_block_device() {
+        Some("bd")
+    } else if file_type.is_char_device() {
+        Some("cd")
+    } else if metadata.permissions().mode() & 0o111 != 0 {
+        Some("ex")
+    } else {
+        None
+    }
+}
@@ -1,29 +1,621 @@
+mod aggregate;
+mod ansi;
+mod bidi;
+mod cache;
+mod command;
+mod config;
+mod delimiter;
+mod diff;
+mod events;
+mod fields;
+mod groups;
+mod history;
+mod items;
+mod keymap;
+mod lscolors;
+mod matcher;
+mod memory;
+mod menu;
+mod paths;
+mod prompt;
+#[cfg(feature = "lua")]
+mod script;
+mod sort;
+mod state;
+mod table;
+mod theme;
+mod tree;
 mod tui_selector;
+mod walk;
 
-use std::io::stdin;
-use std::process::exit;
+use std::collections::HashSet;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::{self, stdin, BufRead, BufReader, Read};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::process::CommandExt;
+use std::process::{exit, Command, ExitStatus, Stdio};
+use std::rc::Rc;
+use std::sync::atomic::{self, AtomicBool, AtomicUsize};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use clap::Parser;
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser};
+use regex::Regex;
+use termion::event::Key;
+
+use aggregate::{parse_budget, parse_quantity, Budget, BudgetSpec, FieldValues};
+use command::{describe_status, kill_group, Template};
+use config::{Config, Source};
+use delimiter::{detect_columns, Delimiter};
+use diff::Side;
+use fields::{parse_field_list, FieldList};
+use groups::Groups;
+use history::Invocation;
+use items::{
+    AnsiStripped, Appended, Appender, Entries, EntryFormat, IdFn, Items, MappedFile, OutputFn,
+    Skipped,
+};
+use keymap::{key_name, parse_binding, parse_key, Action, Binding, Keymap, Profile, SheetFormat};
+use lscolors::LsColors;
+use matcher::{CaseMode, MatchMode, Tiebreak};
+use memory::MemoryGuard;
+#[cfg(feature = "lua")]
+use script::Script;
+use sort::{sort_order, Collation, SelectionOrder, SortMode};
+use state::State;
+use table::Table;
+use theme::{parse_colors, ColorDepth, Colors};
+use tree::Tree;
+use tui_selector::{Gutter, Height, Outcome, SelectorOptions};
 
 /// Text based list selector, reads a list from stdin and prints selected items to stdout
 #[derive(Parser)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, disable_help_subcommand = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Subcommand>,
+    /// Named sources from the config file to list, written as "@NAME", grouped by source
+    /// when several are given (or combined with --file)
+    #[arg(value_parser = parse_source_name)]
+    source: Vec<String>,
     /// Add line numbers
     #[arg(short, long, action = clap::ArgAction::SetTrue)]
     numbering: bool,
+    /// Show the distance of each entry to the cursor line, as vim's relativenumber
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    relative_numbers: bool,
     /// Provide list with format "ID::line\n", output selected IDs
     #[arg(short, long, action = clap::ArgAction::SetTrue)]
     id_mode: bool,
+    /// Show the colors of input lines written with ANSI escape sequences, which are removed
+    /// for matching and from the output
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    ansi: bool,
+    /// Display input lines as they are, instead of removing escape sequences and showing
+    /// control characters as "^X"
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    no_strip: bool,
+    /// Delimiter between fields, i.e., between ID and line content in ID mode
+    #[arg(short, long, default_value = "::")]
+    delimiter: String,
+    /// Interpret the delimiter as a regular expression
+    #[arg(short = 'r', long, action = clap::ArgAction::SetTrue)]
+    regex_delimiter: bool,
+    /// Split lines into the columns of whitespace-aligned input (e.g., "ls -l" output) instead
+    /// of using a delimiter
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with_all = ["delimiter", "regex_delimiter"])]
+    auto_columns: bool,
+    /// Display the fields of each line (after --with-nth) as aligned columns, cutting long ones
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with = "auto_columns")]
+    table: bool,
+    /// Output only the listed fields of selected lines (e.g., "1,3-4,6-")
+    #[arg(short, long, value_parser = parse_field_list)]
+    output_field: Option<FieldList>,
+    /// Display only the listed fields of each line in the selector (e.g., "2-")
+    #[arg(short, long, value_parser = parse_field_list)]
+    with_nth: Option<FieldList>,
+    /// Start with the listed entries selected (e.g., "1,4,7-9")
+    #[arg(short, long, value_parser = parse_field_list)]
+    selected: Option<FieldList>,
+    /// Start with the entries matching the regular expression selected
+    #[arg(long, value_parser = Regex::new)]
+    selected_pattern: Option<Regex>,
+    /// Show the entries matching the regular expression dimmed, without letting them be
+    /// selected
+    #[arg(long, value_parser = Regex::new)]
+    disabled_pattern: Option<Regex>,
+    /// Move the cursor over disabled entries without stopping on them
+    #[arg(long, action = clap::ArgAction::SetTrue, requires = "disabled_pattern")]
+    skip_disabled: bool,
+    /// Maximum amount of entries that can be selected, 1 makes selecting replace the selection
+    #[arg(short, long)]
+    max_select: Option<usize>,
+    /// Minimum amount of entries that must be selected to run the selection
+    #[arg(long)]
+    min_select: Option<usize>,
+    /// Show the sum, average and count of the field (numbered from 1) over the selected
+    /// entries in a footer, numbers may have a size suffix (e.g., "1.5K" or "20M")
+    #[arg(long, value_parser = parse_field_number)]
+    sum: Option<usize>,
+    /// Prevent selecting entries that would bring the total of a numeric field over a limit
+    /// (e.g., "field=3,max=500M"), showing the remaining budget in the header
+    #[arg(long, value_parser = parse_budget)]
+    budget: Option<BudgetSpec>,
+    /// Only view the list (with search, filter and preview), without selecting or printing
+    /// entries
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with_all = ["inline", "selected", "selected_pattern"])]
+    browse: bool,
+    /// Set of keybindings to use
+    #[arg(short, long, value_enum, default_value_t = Profile::Default, global = true)]
+    keys: Profile,
+    /// Bind keys to actions over the --keys profile, e.g., "ctrl-a:select-all,esc:abort",
+    /// actions being named as listed in the README ("ignore" unbinds the key)
+    #[arg(long, value_name = "KEY:ACTION,...", value_delimiter = ',', value_parser = parse_binding, global = true)]
+    bind: Vec<Binding>,
+    /// Ask for confirmation when quitting with entries selected
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    confirm_abort: bool,
+    /// Print a summary (items read, matched, selected, elapsed time, peak memory) to stderr at exit
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    stats: bool,
+    /// Ignore case when searching and matching patterns (default: smart case, ignored unless
+    /// the pattern has uppercase characters)
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with = "case_sensitive")]
+    ignore_case: bool,
+    /// Respect case when searching and matching patterns
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    case_sensitive: bool,
+    /// Command filtering the entries by the typed query ("{q}", appended if missing), reading
+    /// them from stdin and printing the matching ones
+    #[arg(long)]
+    filter_cmd: Option<String>,
+    /// Seconds after which the preview and filter commands are killed, along with the
+    /// processes they started
+    #[arg(long, value_parser = parse_seconds, default_value = "5")]
+    command_timeout: Duration,
+    /// Algorithm matching the search and filter queries
+    #[arg(long, value_enum, default_value_t = MatchMode::Regex)]
+    match_mode: MatchMode,
+    /// Criteria ordering the filtered entries, applied in order to break ties (e.g.,
+    /// "score,length,index")
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "score")]
+    tiebreak: Vec<Tiebreak>,
+    /// Order of the listed entries, by their displayed content (output is unaffected)
+    #[arg(long, value_enum, default_value_t = SortMode::None)]
+    sort: SortMode,
+    /// Rules of the alphabetical order of --sort alpha
+    #[arg(long, value_enum, default_value_t = Collation::Locale)]
+    collation: Collation,
+    /// Reverse the order of the listed entries
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    reverse_sort: bool,
+    /// Wrap long entries over several rows instead of cutting them at the screen width
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with = "inline")]
+    wrap: bool,
+    /// Display the lines as a tree nested by indentation (or by path with --tree-delimiter),
+    /// whose nodes can be collapsed and expanded
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with_all = ["inline", "shuffle", "sample", "sort"])]
+    tree: bool,
+    /// Delimiter between the path components nesting the tree (e.g., "/" for `find` output),
+    /// each node showing only its last component
+    #[arg(long, requires = "tree")]
+    tree_delimiter: Option<String>,
+    /// Select and deselect the descendants of a tree node along with it
+    #[arg(long, action = clap::ArgAction::SetTrue, requires = "tree")]
+    select_descendants: bool,
+    /// Amount of input lines, from the start, pinned below the header line as column titles
+    /// instead of being entries
+    #[arg(long, default_value_t = 0)]
+    header_lines: usize,
+    /// Text shown below the header line, above the entries, spanning several lines if it
+    /// contains newlines (or "\n")
+    #[arg(long, conflicts_with = "inline")]
+    header: Option<String>,
+    /// Hide the keybindings hint from the header line
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    no_hint: bool,
+    /// Colors of the header, selected, cursor, warning, highlight, missing, removed and added
+    /// elements, replacing those of the theme (e.g., "header:bg=blue,cursor:fg=black:bg=cyan")
+    #[arg(long, value_name = "SPEC", value_parser = parse_colors)]
+    color: Option<Colors>,
+    /// Colors used, detected from $COLORTERM and $TERM by default, the theme being adapted to
+    /// them
+    #[arg(long, value_name = "MODE")]
+    color_mode: Option<ColorDepth>,
+    /// Draw without colors, marking selected entries with "*" (same as --color-mode mono,
+    /// also set by $NO_COLOR)
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with = "color_mode")]
+    no_color: bool,
+    /// Color the entries that are file paths by type and extension as ls does, according to
+    /// $LS_COLORS
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    ls_colors: bool,
+    /// Treat the entries as file paths, showing the targets of symlinks and marking broken
+    /// links and missing paths
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    paths: bool,
+    /// Characters pointing at the entry under the cursor [default: >]
+    #[arg(long, value_name = "CHARS")]
+    pointer: Option<String>,
+    /// Characters shown before the selected entries, besides their highlight
+    #[arg(long, value_name = "CHARS")]
+    marker: Option<String>,
+    /// Show a "[x]" or "[ ]" checkbox before each entry
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    checkboxes: bool,
+    /// Hide the header line (position, counts and keybindings hint), leaving its row to the entries;
+    /// warnings still show in its place
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    no_header: bool,
+    /// Show lines starting with this prefix as bold section headers (without the prefix),
+    /// which are not entries (e.g., "--group:")
+    #[arg(long, allow_hyphen_values = true, conflicts_with = "file")]
+    group_marker: Option<String>,
+    /// Show an A–Z index on the right edge, the jump key followed by a letter moves to its
+    /// section (for sorted lists)
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with = "inline")]
+    index: bool,
+    /// Show a scrollbar on the right edge, telling which part of a long list is on screen
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with_all = ["inline", "index"])]
+    scrollbar: bool,
+    /// Scroll the list when the cursor comes within N entries of its top or bottom edge,
+    /// keeping them in view
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    scroll_off: usize,
+    /// Stop the cursor at the first and last entries instead of wrapping around to the other
+    /// end of the list
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    no_cycle: bool,
+    /// List the entries in a random order
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    shuffle: bool,
+    /// List only a random subset of N entries, drawn again with the reshuffle key
+    #[arg(long, value_name = "N")]
+    sample: Option<usize>,
+    /// List entries upwards from the bottom of the screen, with the header at the bottom
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    reverse: bool,
+    /// Show a single prompt line with the best match instead of the list, tab cycles through
+    /// the matches and enter prints the shown one
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with_all = ["height", "preview"])]
+    inline: bool,
+    /// Draw the selector in a window of the provided height (rows, or percentage of the
+    /// terminal height as "N%") below the cursor, instead of the whole screen
+    #[arg(long, value_parser = parse_height)]
+    height: Option<Height>,
+    /// Record the session's input events (keys and their timing) to the file
+    #[arg(long, conflicts_with = "replay")]
+    record: Option<String>,
+    /// Replay the input events recorded to the file instead of reading the keyboard
+    #[arg(long)]
+    replay: Option<String>,
+    /// Command to preview the entry under the cursor, "{}" is replaced by the entry
+    #[arg(short, long)]
+    preview: Option<String>,
+    /// Run command templates directly instead of through "$SHELL -c"
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    no_shell: bool,
+    /// Read NUL-separated records from stdin instead of lines, printing selected records
+    /// NUL-terminated
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with_all = ["record_separator", "file"])]
+    read0: bool,
+    /// Separator between the records read from stdin instead of newlines (e.g., "\n\n" for
+    /// blank lines, with CRLF line endings too), also terminating the printed records; the list
+    /// shows the first line of each record
+    #[arg(long, conflicts_with = "file")]
+    record_separator: Option<String>,
+    /// Command stdin is piped through before being split into lines or records, e.g., to turn
+    /// a JSON array into lines with "jq -r '.[]'"
+    #[arg(long, value_name = "COMMAND", conflicts_with_all = ["file", "source", "glob", "diff_lists"])]
+    transform: Option<String>,
+    /// Order of the printed entries: that in which they were selected, that of the input, or
+    /// that in which they are listed (e.g., after moving them)
+    #[arg(long, value_enum, default_value_t = SelectionOrder::Selected)]
+    selection_order: SelectionOrder,
+    /// Keys that also run the selection, named as in the header hint (e.g., "ctrl-o,ctrl-d"),
+    /// the first printed line being the name of the key pressed (empty for the usual key)
+    #[arg(long, value_name = "KEYS", value_delimiter = ',', value_parser = parse_key_arg)]
+    expect: Vec<Key>,
+    /// Print the selected entries in groups of at most N, separated by --chunk-separator
+    #[arg(long, value_name = "N", value_parser = parse_field_number)]
+    chunk: Option<usize>,
+    /// Line printed between the groups of --chunk
+    #[arg(long, default_value = "", requires = "chunk")]
+    chunk_separator: String,
+    /// Write the groups of --chunk to numbered files starting with the prefix (e.g., "part-"
+    /// writes part-1, part-2...) instead of printing them
+    #[arg(long, value_name = "PREFIX", requires = "chunk")]
+    chunk_files: Option<String>,
+    /// Run the command for each group of --chunk selected entries (all of them without it),
+    /// "{+}" being replaced by the entries (appended if missing), instead of printing them
+    #[arg(long, value_name = "COMMAND", conflicts_with = "chunk_files")]
+    exec_chunked: Option<String>,
+    /// Run the command for each selected entry, "{}" being replaced by the entry (appended if
+    /// missing), after confirming it, instead of printing them
+    #[arg(long, value_name = "COMMAND", conflicts_with_all = ["exec_chunked", "chunk"])]
+    exec: Option<String>,
+    /// Amount of --exec commands run at the same time
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = parse_jobs, requires = "exec")]
+    jobs: usize,
+    /// Show the whole record under the cursor below the list, for multi-line records
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with = "preview")]
+    detail: bool,
+    /// Read the list from a file instead of stdin, memory-mapping it; given several times,
+    /// the files are listed one after the other, grouped by file
+    #[arg(short, long)]
+    file: Vec<String>,
+    /// Print the name of the input (file path, "@SOURCE", glob pattern or "-" for stdin) of
+    /// each selected entry before it, separated by a tab
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    output_with_source: bool,
+    /// Compare two lists, listing the lines of A and then those only in B, marked by the list
+    /// they are in; the common lines are selected at first, the selected ones being printed
+    #[arg(long, num_args = 2, value_names = ["A", "B"], conflicts_with_all = ["file", "source", "glob", "header_lines", "output_with_source", "read0", "record_separator"])]
+    diff_lists: Option<Vec<String>>,
+    /// Cache the line index of --file next to it, speeding up opening it again
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    cache_index: bool,
+    /// Refuse to load more of stdin than fits in this much memory (e.g., "2G"), as well as
+    /// more than the memory available
+    #[arg(long, value_name = "SIZE", value_parser = parse_memory)]
+    max_memory: Option<u64>,
+    /// List the files matching the glob pattern (e.g., "**/*.rs") instead of reading stdin,
+    /// "*" not matching across directories and "**" matching any of them
+    #[arg(long, value_name = "PATTERN", conflicts_with_all = ["file", "source", "read0", "record_separator"])]
+    glob: Option<String>,
+    /// Skip the files ignored by git (.gitignore files and the repository excludes) or by
+    /// .ignore files with --glob, until toggled with a key
+    #[arg(long, action = clap::ArgAction::SetTrue, requires = "glob")]
+    gitignore: bool,
+    /// List hidden files (starting with ".") with --glob, otherwise shown with a key
+    #[arg(long, action = clap::ArgAction::SetTrue, requires = "glob")]
+    hidden: bool,
+    /// Write the final state (query, selection, cursor, counts and closing key) as JSON to
+    /// this file when exiting, whether the selection is accepted or not
+    #[arg(long, value_name = "FILE")]
+    dump_state: Option<String>,
+    /// Restore the selection and cursor of a state written with --dump-state, possibly on
+    /// another machine, finding the entries by the hash of their line
+    #[arg(long, value_name = "FILE")]
+    resume: Option<String>,
+    /// Named pipe (FIFO) whose lines are appended to the list while the selector is running,
+    /// skipping lines whose ID (or whole line) is already listed
+    #[arg(long, value_name = "PATH", conflicts_with = "tree")]
+    append_fifo: Option<String>,
+    /// Lua script defining hooks (on_load, transform_item, on_toggle) and custom actions
+    #[cfg(feature = "lua")]
+    #[arg(long)]
+    script: Option<String>,
 }
 
-/// Returns the provided vector with respective line numbering at the beginning of each string.
-fn add_numbering(entry_list: &[String]) -> Vec<String> {
-    entry_list
-        .iter()
-        .enumerate()
-        .map(|(i, e)| format!(" {} {}", get_num_str(i + 1, entry_list.len()), e.clone()))
-        .collect()
+/// Returns the window height from its "N" or "N%" argument.
+fn parse_height(arg: &str) -> Result<Height, String> {
+    let invalid =
+        |_| format!("invalid height '{arg}', expected rows or a percentage (e.g., 15 or 40%)");
+    match arg.strip_suffix('%') {
+        Some(percent) => percent.parse().map(Height::Percent).map_err(invalid),
+        None => arg.parse().map(Height::Rows).map_err(invalid),
+    }
+}
+
+/// Returns the field number from its argument, which must be at least 1.
+fn parse_field_number(arg: &str) -> Result<usize, String> {
+    match arg.parse() {
+        Ok(0) | Err(_) => Err(format!("invalid field '{arg}', numbering starts at 1")),
+        Ok(n) => Ok(n),
+    }
+}
+
+/// Returns the amount of jobs from its argument, a positive number.
+fn parse_jobs(arg: &str) -> Result<usize, String> {
+    match arg.parse() {
+        Ok(0) | Err(_) => Err(format!(
+            "invalid amount of jobs '{arg}', expected a positive number"
+        )),
+        Ok(n) => Ok(n),
+    }
+}
+
+/// Returns the duration from its argument, a positive amount of seconds.
+fn parse_seconds(arg: &str) -> Result<Duration, String> {
+    match arg.parse::<f64>() {
+        Ok(secs) if secs > 0.0 && secs.is_finite() => Ok(Duration::from_secs_f64(secs)),
+        _ => Err(format!(
+            "invalid duration '{arg}', expected a positive amount of seconds"
+        )),
+    }
+}
+
+/// Returns the amount of bytes from its argument, a number with an optional size suffix.
+fn parse_memory(arg: &str) -> Result<u64, String> {
+    match parse_quantity(arg) {
+        Some((bytes, _)) if bytes >= 1.0 => Ok(bytes as u64),
+        _ => Err(format!(
+            "invalid size '{arg}', expected e.g. \"512M\" or \"2G\""
+        )),
+    }
+}
+
+/// Exits with an error telling that the input does not fit in the memory limit, which is
+/// that of --max-memory or the memory available.
+fn refuse_input(limit: u64, max_memory: Option<u64>) -> ! {
+    let reason = if max_memory == Some(limit) {
+        "the --max-memory limit"
+    } else {
+        "the memory available"
+    };
+    eprintln!(
+        "tui_selector: error: loading the input would take more than {} ({reason}); save it to a file and open it with --file, which maps it instead of loading it",
+        memory::format_size(limit)
+    );
+    exit(1);
+}
+
+/// Returns the keymap of the `--keys` profile with the `--bind` bindings applied.
+fn keymap(args: &Args) -> Keymap {
+    let mut keymap = Keymap::for_profile(args.keys);
+    keymap.apply(&args.bind);
+    keymap
+}
+
+/// Returns the key from its argument, named as in the header hint.
+fn parse_key_arg(arg: &str) -> Result<Key, String> {
+    parse_key(arg).ok_or(format!("unknown key '{arg}'"))
+}
+
+/// Alternative modes of operation.
+#[derive(clap::Subcommand)]
+enum Subcommand {
+    /// List past invocations reading a file or source, running the chosen one again
+    Recent,
+    /// Print a cheat sheet of the keybindings of the profile chosen with --keys
+    Keys {
+        /// Format of the cheat sheet
+        #[arg(long, value_enum, default_value_t = SheetFormat::Table)]
+        export: SheetFormat,
+    },
+}
+
+/// Amount of lines, from the start of the input, where columns are detected with
+/// `--auto-columns` or measured with `--table`.
+const COLUMNS_SAMPLE: usize = 1000;
+
+/// Exit status when there is no terminal to show the selector on, telling wrapping tools
+/// that it needs an interactive terminal.
+const NO_TTY_EXIT_CODE: i32 = 3;
+
+/// Exits with a JSON error on stderr, for wrapping tools to read, unless both the terminal
+/// the selector reads keys from and stdout, which it is drawn on, are available.
+fn require_tty() {
+    let error = if !atty::is(atty::Stream::Stdout) {
+        String::from("stdout is not a terminal")
+    } else if let Err(e) = termion::get_tty() {
+        format!("unable to open the terminal: {e}")
+    } else {
+        return;
+    };
+    let error = serde_json::json!({
+        "code": "no_tty",
+        "message": error,
+        "hint": "run tui_selector in an interactive terminal, with stdout left on it",
+    });
+    eprintln!("{error}");
+    exit(NO_TTY_EXIT_CODE);
+}
+
+/// Returns the source name from its "@NAME" argument.
+fn parse_source_name(arg: &str) -> Result<String, String> {
+    match arg.strip_prefix('@') {
+        Some(name) if !name.is_empty() => Ok(name.to_string()),
+        _ => Err(String::from("sources are written as \"@NAME\"")),
+    }
+}
+
+/// Returns the name of the input the line at the provided index was read from, the inputs
+/// being listed one after the other with their amount of lines.
+fn input_name(inputs: &[(String, usize)], mut idx: usize) -> Option<&str> {
+    for (name, len) in inputs {
+        if idx < *len {
+            return Some(name);
+        }
+        idx -= len;
+    }
+    None
+}
+
+/// Returns the output lines of the source command.
+fn read_source(source: &Source, use_shell: bool) -> Result<Vec<String>, Box<dyn Error>> {
+    let output = Template::new(&source.cmd, use_shell)
+        .plain_command()
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(format!("'{}' failed ({})", source.cmd, output.status).into());
+    }
+    let lines = String::from_utf8_lossy(&output.stdout);
+    Ok(lines.lines().map(|l| l.trim().to_string()).collect())
+}
+
+/// Returns the colors used: those forced with `--color-mode` or `--no-color`, otherwise
+/// those detected from the environment.
+fn color_depth(args: &Args) -> ColorDepth {
+    if args.no_color {
+        return ColorDepth::Mono;
+    }
+    args.color_mode.unwrap_or_else(ColorDepth::detect)
+}
+
+/// Lets the user choose a past invocation from the history and runs it again, exiting with
+/// its exit code.
+fn run_recent(args: &Args) -> ! {
+    let invocations = match history::recent() {
+        Ok(invocations) if !invocations.is_empty() => invocations,
+        Ok(_) => {
+            eprintln!("tui_selector: error: no recent invocations");
+            exit(1);
+        }
+        Err(e) => {
+            eprintln!("tui_selector: error: unable to read history: {e}");
+            exit(1);
+        }
+    };
+    let lines: Vec<String> = invocations.iter().map(Invocation::display).collect();
+    let total = lines.len();
+    let entries = Entries {
+        items: Rc::new(lines),
+        format: make_entry_format(total, args.numbering, None, None, None),
+        colored: None,
+        sanitize: true,
+    };
+    let options = SelectorOptions {
+        order: (0..total).collect(),
+        header_line: true,
+        hint: true,
+        reverse: args.reverse,
+        inline: args.inline,
+        height: args.height,
+        record: args.record.clone(),
+        max_select: Some(1),
+        min_select: Some(1),
+        keymap: keymap(args),
+        colors: args.color.clone().unwrap_or_default(),
+        color_depth: color_depth(args),
+        match_mode: args.match_mode,
+        tiebreak: args.tiebreak.clone(),
+        command_timeout: args.command_timeout,
+        ..Default::default()
+    };
+    require_tty();
+    let Ok(outcome) = tui_selector::select(entries, options) else {
+        eprintln!("tui_selector: error: unable to access tty i/o.");
+        exit(1);
+    };
+    let Some(&chosen) = outcome.selection.as_ref().and_then(|s| s.first()) else {
+        exit(0);
+    };
+
+    let invocation = &invocations[chosen];
+    let status = env::current_exe().and_then(|exe| {
+        Command::new(exe)
+            .args(&invocation.args)
+            .current_dir(&invocation.dir)
+            .status()
+    });
+    match status {
+        Ok(status) => exit(status.code().unwrap_or(1)),
+        Err(e) => {
+            eprintln!(
+                "tui_selector: error: unable to run '{}': {e}",
+                invocation.display()
+            );
+            exit(1);
+        }
+    }
 }
 
 /// Returns string with padded number, adjusting string length with zeroes to the left of the
@@ -35,54 +627,882 @@ fn get_num_str(n: usize, max_n: usize) -> String {
     adj_str
 }
 
-/// Returns formatted content for displaying it in the selector, with line numbering and
-/// hiding the ID (if required).
-fn prepare_selector_content(input_stream: &[String], add_num: bool, id_out: bool) -> Vec<String> {
-    let mut selector_content = if id_out {
-        input_stream
-            .iter()
-            .map(|l| l.split_once("::").unwrap_or(("", l)).1.to_string())
-            .collect()
+/// Returns the function formatting each line for displaying it in the selector, with line
+/// numbering and showing only the chosen fields or hiding the ID (if required), aligned in
+/// columns with a table.
+fn make_entry_format(
+    total: usize,
+    add_num: bool,
+    id_delimiter: Option<Delimiter>,
+    display_fields: Option<(FieldList, Delimiter)>,
+    table: Option<Table>,
+) -> EntryFormat {
+    Box::new(move |idx, line| {
+        let mut content = if let Some((fields, delimiter)) = &display_fields {
+            fields.extract(line, delimiter)
+        } else if let Some(delimiter) = &id_delimiter {
+            delimiter
+                .split_once(line)
+                .unwrap_or(("", line))
+                .1
+                .to_string()
+        } else {
+            line.to_string()
+        };
+        if let Some(table) = &table {
+            content = table.format(&content);
+        }
+
+        if add_num {
+            format!(" {} {}", get_num_str(idx + 1, total), content)
+        } else {
+            content
+        }
+    })
+}
+
+/// Returns the peak resident memory of the process in bytes, if available.
+fn peak_memory() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmHWM:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+/// Exit status when some command run with --exec or --exec-chunked fails, as for xargs.
+const CHUNK_FAILED_EXIT_CODE: i32 = 123;
+
+/// Runs the command for each of the outputs, at most `jobs` at a time, reporting those that
+/// fail to stderr, and exits.
+fn run_each(template: &Template, outputs: &[String], jobs: usize) -> ! {
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    // the commands run in process groups of their own, out of reach of ctrl-c, which kills
+    // their groups instead and leaves the remaining entries out
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let _ = signal_hook::flag::register(signal_hook::consts::SIGINT, Arc::clone(&interrupted));
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(outputs.len()) {
+            scope.spawn(|| {
+                while let Some(output) = outputs
+                    .get(next.fetch_add(1, atomic::Ordering::Relaxed))
+                    .filter(|_| !interrupted.load(atomic::Ordering::Relaxed))
+                {
+                    let failure = match status_in_group(template.item_command(output), &interrupted)
+                    {
+                        Ok(status) if status.success() => continue,
+                        Ok(status) => format!("failed ({})", describe_status(status)),
+                        Err(e) => format!("failed to run: {e}"),
+                    };
+                    failed.store(true, atomic::Ordering::Relaxed);
+                    eprintln!("tui_selector: exec for '{output}': {failure}");
+                }
+            });
+        }
+    });
+    exit(if failed.into_inner() {
+        CHUNK_FAILED_EXIT_CODE
     } else {
-        input_stream.to_owned()
-    };
+        0
+    })
+}
+
+/// Runs the command in a process group of its own with stdin closed, returning its exit
+/// status, its group being killed once interrupted.
+fn status_in_group(mut cmd: Command, interrupted: &AtomicBool) -> io::Result<ExitStatus> {
+    let mut child = cmd.process_group(0).stdin(Stdio::null()).spawn()?;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if interrupted.load(atomic::Ordering::Relaxed) {
+            kill_group(child.id());
+            return child.wait();
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
 
-    if add_num {
-        selector_content = add_numbering(&selector_content);
+/// Runs the command for each chunk of the provided size of the outputs, reporting the status
+/// of each run to stderr, and exits.
+fn run_chunked(template: &Template, outputs: &[String], chunk_size: usize) -> ! {
+    let chunks: Vec<&[String]> = outputs.chunks(chunk_size).collect();
+    let mut failed = false;
+    for (n, chunk) in chunks.iter().enumerate() {
+        let status = match template.multi_command(chunk).stdin(Stdio::null()).status() {
+            Ok(status) if status.success() => String::from("ok"),
+            Ok(status) => {
+                failed = true;
+                format!("failed ({})", describe_status(status))
+            }
+            Err(e) => {
+                failed = true;
+                format!("failed to run: {e}")
+            }
+        };
+        eprintln!(
+            "tui_selector: chunk {}/{} ({} items): {status}",
+            n + 1,
+            chunks.len(),
+            chunk.len()
+        );
     }
+    exit(if failed { CHUNK_FAILED_EXIT_CODE } else { 0 })
+}
 
-    selector_content
+/// Returns the text of each chunk of the provided size of the outputs, every output being
+/// followed by the terminator.
+fn chunk_texts(outputs: &[String], chunk_size: usize, terminator: &str) -> Vec<String> {
+    outputs
+        .chunks(chunk_size)
+        .map(|chunk| chunk.iter().map(|o| format!("{o}{terminator}")).collect())
+        .collect()
+}
+
+/// Prints a one-line summary of the run to stderr.
+fn print_stats(items_read: usize, outcome: &Outcome, start: Instant) {
+    let selected = outcome.selection.as_ref().map_or(0, Vec::len);
+    let memory = peak_memory().map_or(String::from("n/a"), |b| {
+        format!("{:.1} MiB", b as f64 / 1048576.0)
+    });
+    eprintln!(
+        "tui_selector: stats: {items_read} items read, {} matched, {selected} selected, {:.3}s elapsed, {memory} peak memory",
+        outcome.matched,
+        start.elapsed().as_secs_f64()
+    );
 }
 
 fn main() {
-    let args = Args::parse();
+    let start = Instant::now();
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    match args.command {
+        Some(Subcommand::Recent) => run_recent(&args),
+        Some(Subcommand::Keys { export }) => {
+            print!("{}", keymap(&args).cheat_sheet(export));
+            exit(0);
+        }
+        None => {}
+    }
+
+    let mut config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("tui_selector: error: invalid config file: {e}");
+            exit(1);
+        }
+    };
+    let color_depth = color_depth(&args);
+    // options given on the command line take precedence over the config file, and selected
+    // entries are marked when they cannot be colored
+    let mut gutter = Gutter {
+        pointer: args
+            .pointer
+            .clone()
+            .or(config.pointer.take())
+            .unwrap_or(Gutter::default().pointer),
+        marker: args
+            .marker
+            .clone()
+            .or(config.marker.take())
+            .or_else(|| (color_depth == ColorDepth::Mono).then(|| String::from("*"))),
+        relative_numbers: None,
+        checkboxes: args.checkboxes || config.checkboxes,
+    };
+    let mut colors = match config.color.as_deref().map(parse_colors) {
+        Some(Ok(colors)) => colors,
+        Some(Err(e)) => {
+            eprintln!("tui_selector: error: invalid config file: color: {e}");
+            exit(1);
+        }
+        None => Colors::default(),
+    };
+    colors
+        .0
+        .extend(args.color.take().map(|c| c.0).unwrap_or_default());
+    let menu = match menu::build(config.menu, !args.no_shell) {
+        Ok(menu) => menu,
+        Err(e) => {
+            eprintln!("tui_selector: error: invalid config file: menu: {e}");
+            exit(1);
+        }
+    };
 
-    // abort if no stdin pipe is provided
-    if atty::is(atty::Stream::Stdin) {
-        eprintln!("tui_selector: error: stdin buffer is empty, no input list provided.");
+    let mut source_lines = Vec::new();
+    for (n, name) in args.source.clone().into_iter().enumerate() {
+        let Some(source) = config.sources.remove(&name) else {
+            eprintln!("tui_selector: error: unknown source '@{name}'");
+            exit(1);
+        };
+        // options given on the command line take precedence over the first source's settings
+        if n == 0 {
+            if let Some(d) = &source.delimiter {
+                if matches.value_source("delimiter") != Some(ValueSource::CommandLine) {
+                    args.delimiter = d.clone();
+                }
+            }
+            if let (None, Some(columns)) = (&args.with_nth, &source.columns) {
+                let spec: Vec<String> = columns.iter().map(usize::to_string).collect();
+                match parse_field_list(&spec.join(",")) {
+                    Ok(fields) => args.with_nth = Some(fields),
+                    Err(e) => {
+                        eprintln!("tui_selector: error: invalid columns of source '@{name}': {e}");
+                        exit(1);
+                    }
+                }
+            }
+        }
+        match read_source(&source, !args.no_shell) {
+            Ok(lines) => source_lines.push((format!("@{name}"), lines)),
+            Err(e) => {
+                eprintln!("tui_selector: error: unable to read source '@{name}': {e}");
+                exit(1);
+            }
+        }
+    }
+    let mut files = Vec::new();
+    for path in &args.file {
+        match MappedFile::open(path, args.cache_index) {
+            Ok(file) => files.push((path.clone(), file)),
+            Err(e) => {
+                eprintln!("tui_selector: error: unable to read file '{path}': {e}");
+                exit(1);
+            }
+        }
+    }
+    if source_lines.len() + files.len() > 1 && args.group_marker.is_some() {
+        eprintln!("tui_selector: error: --group-marker cannot be used with several inputs, which are grouped by input");
         exit(1);
     }
 
-    let input_stream: Vec<String> = stdin()
-        .lines()
-        .filter(Result::is_ok)
-        .map(|l| l.unwrap().trim().to_string())
+    let mut delimiter = match Delimiter::new(&args.delimiter, args.regex_delimiter) {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("tui_selector: error: invalid delimiter: {e}");
+            exit(1);
+        }
+    };
+    let record_separator = if args.read0 {
+        Some(String::from("\0"))
+    } else {
+        args.record_separator.as_ref().map(|s| {
+            s.replace("\\n", "\n")
+                .replace("\\r", "\r")
+                .replace("\\t", "\t")
+                .replace("\\0", "\0")
+        })
+    };
+    // group header lines are taken out of the list, so they are never counted as entries
+    let mut groups = None;
+    let mut listing = None;
+    let mut sides = None;
+    let mut split_groups = |lines: Vec<String>| -> Rc<dyn Items> {
+        match &args.group_marker {
+            Some(marker) => {
+                let (lines, found) = Groups::split(lines, marker);
+                groups = Some(found);
+                Rc::new(lines)
+            }
+            None => Rc::new(lines),
+        }
+    };
+    // names of the inputs, with the amount of lines read from each
+    let mut inputs: Vec<(String, usize)> = Vec::new();
+    let items: Rc<dyn Items> = if let Some([first, second]) = args.diff_lists.as_deref() {
+        let read = |path: &str| match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) => {
+                eprintln!("tui_selector: error: unable to read file '{path}': {e}");
+                exit(1);
+            }
+        };
+        let (lines, found) = diff::merge(&read(first), &read(second));
+        sides = Some(found);
+        Rc::new(lines)
+    } else if source_lines.len() + files.len() > 1 {
+        let mut lines = Vec::new();
+        for (name, input) in source_lines {
+            inputs.push((name, input.len()));
+            lines.extend(input);
+        }
+        for (path, file) in files {
+            inputs.push((path, file.len()));
+            lines.extend((0..file.len()).map(|i| file.get(i).into_owned()));
+        }
+        Rc::new(lines)
+    } else if let Some((path, file)) = files.pop() {
+        inputs.push((path, 0));
+        Rc::new(file)
+    } else if let Some((name, lines)) = source_lines.pop() {
+        inputs.push((name, 0));
+        split_groups(lines)
+    } else if let Some(pattern) = &args.glob {
+        match walk::glob(pattern, !args.gitignore, args.hidden) {
+            Ok((paths, _)) if paths.is_empty() => {
+                eprintln!("tui_selector: error: no files match '{pattern}'");
+                exit(1);
+            }
+            Ok((paths, found)) => {
+                inputs.push((pattern.clone(), 0));
+                listing = Some(found);
+                split_groups(paths)
+            }
+            Err(e) => {
+                eprintln!("tui_selector: error: invalid glob '{pattern}': {e}");
+                exit(1);
+            }
+        }
+    } else {
+        // abort if no stdin pipe is provided
+        if atty::is(atty::Stream::Stdin) {
+            eprintln!("tui_selector: error: stdin buffer is empty, no input list provided.");
+            exit(1);
+        }
+
+        // the input is refused before it takes all the memory, from its size if it is a file
+        let limit = match (args.max_memory, memory::available()) {
+            (Some(max), Some(available)) => Some(max.min(available)),
+            (max, available) => max.or(available),
+        };
+        if let (Some(limit), None) = (limit, &args.transform) {
+            let size = fs::metadata("/proc/self/fd/0")
+                .ok()
+                .filter(|m| m.is_file())
+                .map(|m| m.len());
+            if size.is_some_and(|size| memory::estimate(size, 0) > limit) {
+                refuse_input(limit, args.max_memory);
+            }
+        }
+        // the transform command reads stdin itself, its output being read instead
+        let mut transform = args.transform.as_ref().map(|cmd| {
+            let child = Template::new(cmd, !args.no_shell)
+                .plain_command()
+                .stdout(Stdio::piped())
+                .stderr(Stdio::inherit())
+                .spawn();
+            match child {
+                Ok(child) => (cmd, child),
+                Err(e) => {
+                    eprintln!("tui_selector: error: unable to run transform command '{cmd}': {e}");
+                    exit(1);
+                }
+            }
+        });
+        let input: Box<dyn Read> = match transform
+            .as_mut()
+            .and_then(|(_, child)| child.stdout.take())
+        {
+            Some(output) => Box::new(output),
+            None => Box::new(stdin().lock()),
+        };
+        let mut guard = MemoryGuard::new(input, limit.unwrap_or(u64::MAX));
+        let input_stream: Vec<String> = match &record_separator {
+            Some(separator) => {
+                let mut input = String::new();
+                if let Err(e) = guard.read_to_string(&mut input) {
+                    eprintln!("tui_selector: error: unable to read stdin: {e}");
+                    exit(1);
+                }
+                // CRLF line endings are read as newlines, also in the separator
+                let input = input.replace("\r\n", "\n");
+                input
+                    .split(separator.replace("\r\n", "\n").as_str())
+                    .map(|r| r.trim().to_string())
+                    .filter(|r| !r.is_empty())
+                    .collect()
+            }
+            // the indentation nests the tree when it has no delimiter
+            None if args.tree && args.tree_delimiter.is_none() => BufReader::new(&mut guard)
+                .lines()
+                .filter(Result::is_ok)
+                .map(|l| l.unwrap().trim_end().to_string())
+                .collect(),
+            None => BufReader::new(&mut guard)
+                .lines()
+                .filter(Result::is_ok)
+                .map(|l| l.unwrap().trim().to_string())
+                .collect(),
+        };
+        if guard.exceeded() {
+            refuse_input(limit.unwrap_or_default(), args.max_memory);
+        }
+        if let Some((cmd, mut child)) = transform {
+            match child.wait() {
+                Ok(status) if status.success() => {}
+                Ok(status) => {
+                    eprintln!(
+                        "tui_selector: error: transform command '{cmd}' failed ({})",
+                        describe_status(status)
+                    );
+                    exit(1);
+                }
+                Err(e) => {
+                    eprintln!("tui_selector: error: unable to run transform command '{cmd}': {e}");
+                    exit(1);
+                }
+            }
+        }
+        inputs.push((String::from("-"), 0));
+        split_groups(input_stream)
+    };
+    // a single input holds all the lines, without its group markers
+    if let [(_, len)] = inputs.as_mut_slice() {
+        *len = items.len();
+    }
+    // lines written to the FIFO are added after the input lines
+    let appended = args.append_fifo.as_ref().map(|path| {
+        if !fs::metadata(path).is_ok_and(|m| m.file_type().is_fifo()) {
+            eprintln!("tui_selector: error: '{path}' is not a named pipe (create it with mkfifo)");
+            exit(1);
+        }
+        Rc::new(Appended::new(Rc::clone(&items)))
+    });
+    let items: Rc<dyn Items> = match &appended {
+        Some(appended) => Rc::clone(appended) as Rc<dyn Items>,
+        None => items,
+    };
+    // colored lines are only displayed, everything else uses the lines without escapes
+    // header lines are pinned above the list, they are not entries
+    let header_lines: Vec<String> = (0..args.header_lines.min(items.len()))
+        .map(|i| ansi::strip(&items.get(i)))
         .collect();
+    let items: Rc<dyn Items> = if args.header_lines > 0 {
+        Rc::new(Skipped(items, args.header_lines))
+    } else {
+        items
+    };
+    if inputs.len() > 1 {
+        groups = Some(Groups::from_inputs(&inputs, args.header_lines));
+    }
+    // distances to the cursor are at most the amount of entries
+    if args.relative_numbers {
+        gutter.relative_numbers = Some(items.len().to_string().len());
+    }
+    let colored = args.ansi.then(|| Rc::clone(&items));
+    let items: Rc<dyn Items> = match &colored {
+        Some(colored) => Rc::new(AnsiStripped(Rc::clone(colored))),
+        None => items,
+    };
+
+    if args.auto_columns {
+        let mut sample = header_lines.clone();
+        sample.extend((0..items.len().min(COLUMNS_SAMPLE)).map(|i| items.get(i).into_owned()));
+        delimiter = Delimiter::Columns(detect_columns(&sample));
+    }
+    let id_delimiter = if args.id_mode { Some(&delimiter) } else { None };
+
+    #[cfg(feature = "lua")]
+    let script = args.script.as_ref().map(|path| {
+        let script = Script::load(path).and_then(|script| {
+            script.on_load(items.len())?;
+            Ok(script)
+        });
+        match script {
+            Ok(script) => Rc::new(script),
+            Err(e) => {
+                eprintln!("tui_selector: error: unable to load script '{path}': {e}");
+                exit(1);
+            }
+        }
+    });
+
+    let sort_key = make_entry_format(
+        items.len(),
+        false,
+        id_delimiter.cloned(),
+        args.with_nth.clone().map(|f| (f, delimiter.clone())),
+        None,
+    );
+    let table = args.table.then(|| {
+        let mut sample: Vec<String> = header_lines.iter().map(|l| sort_key(0, l)).collect();
+        sample.extend(
+            (0..items.len().min(COLUMNS_SAMPLE))
+                .map(|i| sort_key(i, items.get(i).lines().next().unwrap_or_default())),
+        );
+        Table::new(&sample, delimiter.clone())
+    });
+    // header lines show the same fields as the entries, aligned in the same table and past
+    // the gutter
+    let mut header: Vec<String> = args
+        .header
+        .as_ref()
+        .map(|h| h.replace("\\n", "\n").lines().map(str::to_string).collect())
+        .unwrap_or_default();
+    header.extend(header_lines.iter().map(|line| {
+        let content = sort_key(0, line);
+        let indent = " ".repeat(gutter.width());
+        match &table {
+            Some(table) => format!("{indent}{}", table.format(&content)),
+            None => format!("{indent}{content}"),
+        }
+    }));
+    let tree = args
+        .tree
+        .then(|| Rc::new(Tree::new(items.as_ref(), args.tree_delimiter.clone())));
+    let format = make_entry_format(
+        items.len(),
+        args.numbering,
+        id_delimiter.cloned(),
+        args.with_nth.clone().map(|f| (f, delimiter.clone())),
+        table,
+    );
+    #[cfg(feature = "lua")]
+    let format: EntryFormat = match &script {
+        Some(script) => {
+            let script = Rc::clone(script);
+            Box::new(move |idx, line| format(idx, &script.transform_item(idx, line)))
+        }
+        None => format,
+    };
+    // multi-line records are listed by their first line
+    let format: EntryFormat = if record_separator.is_some() {
+        Box::new(move |idx, record| format(idx, record.lines().next().unwrap_or_default()))
+    } else {
+        format
+    };
+    // tree nodes are displayed by their last path component
+    let format: EntryFormat = match &tree {
+        Some(tree) => {
+            let tree = Rc::clone(tree);
+            Box::new(move |idx, line| format(idx, &tree.label(idx, line)))
+        }
+        None => format,
+    };
+    let entries = Entries {
+        items: Rc::clone(&items),
+        format,
+        colored,
+        sanitize: !args.no_strip,
+    };
+
+    let order = sort_order(
+        items.len(),
+        |idx| sort_key(idx, &items.get(idx)),
+        args.sort,
+        args.collation,
+        args.reverse_sort,
+    );
+
+    let mut selected = args
+        .selected
+        .as_ref()
+        .map(|s| s.indices(items.len()))
+        .unwrap_or_default();
+    let mut cursor = None;
+    let mut warning = None;
+    if let Some(path) = &args.resume {
+        let state = match State::load(path) {
+            Ok(state) => state,
+            Err(e) => {
+                eprintln!("tui_selector: error: unable to read state from '{path}': {e}");
+                exit(1);
+            }
+        };
+        let (found, found_cursor) = state.locate(items.as_ref());
+        let missing = found.iter().filter(|idx| idx.is_none()).count();
+        if missing > 0 {
+            warning = Some(format!(
+                "{missing} of the resumed selected entries are no longer listed"
+            ));
+        }
+        for idx in found.into_iter().flatten() {
+            if !selected.contains(&idx) {
+                selected.push(idx);
+            }
+        }
+        cursor = found_cursor;
+    }
+    // the lines of both lists are kept unless deselected
+    if let Some(sides) = &sides {
+        for (i, _) in sides
+            .iter()
+            .enumerate()
+            .filter(|(_, &side)| side == Side::Common)
+        {
+            if !selected.contains(&i) {
+                selected.push(i);
+            }
+        }
+    }
+    if let Some(pattern) = &args.selected_pattern {
+        for i in 0..items.len() {
+            if pattern.is_match(&items.get(i)) && !selected.contains(&i) {
+                selected.push(i);
+            }
+        }
+    }
+    let disabled: HashSet<usize> = match &args.disabled_pattern {
+        Some(pattern) => (0..items.len())
+            .filter(|&i| pattern.is_match(&items.get(i)))
+            .collect(),
+        None => HashSet::new(),
+    };
+
+    let appender = appended.map(|appended| {
+        let id_delimiter = id_delimiter.cloned();
+        let id: IdFn = Box::new(move |line| {
+            let line = ansi::strip(line);
+            match &id_delimiter {
+                Some(delimiter) => delimiter
+                    .split_once(&line)
+                    .unwrap_or((&line, ""))
+                    .0
+                    .to_string(),
+                None => line,
+            }
+        });
+        Appender::new(appended, items.as_ref(), id, args.disabled_pattern.clone())
+    });
+
+    let mut keymap = keymap(&args);
+    #[cfg(feature = "lua")]
+    if let Some(script) = &script {
+        for (i, &key) in script.action_keys().iter().enumerate() {
+            keymap.bind(key, Action::Script(i));
+        }
+    }
+
+    if args.glob.is_none() {
+        keymap.unbind(&[Action::ToggleIgnored, Action::ToggleHidden]);
+    }
+    if menu.is_empty() {
+        keymap.unbind(&[Action::Menu]);
+    }
+    if args.browse {
+        keymap.unbind(&[
+            Action::Toggle,
+            Action::SelectAll,
+            Action::SelectNone,
+            Action::SelectPattern,
+            Action::ShowOutput,
+            Action::Accept,
+        ]);
+    }
+
+    let replay = args
+        .replay
+        .as_ref()
+        .map(|path| match events::read_recording(path) {
+            Ok(recording) => recording,
+            Err(e) => {
+                eprintln!("tui_selector: error: unable to read recording '{path}': {e}");
+                exit(1);
+            }
+        });
+
+    // the outputs shown in the selector are formatted as those printed
+    let output_line: Rc<dyn Fn(usize) -> String> = {
+        let items = items.clone();
+        let output_field = args.output_field.clone();
+        let id_delimiter = id_delimiter.cloned();
+        let delimiter = delimiter.clone();
+        let inputs = inputs.clone();
+        let output_with_source = args.output_with_source;
+        let header_lines = args.header_lines;
+        let append_fifo = args.append_fifo.clone();
+        Rc::new(move |i| {
+            let item = items.get(i);
+            let item: &str = &item;
+            let output = if let Some(fields) = &output_field {
+                fields.extract(item, &delimiter)
+            } else if let Some(delimiter) = &id_delimiter {
+                delimiter
+                    .split_once(item)
+                    .unwrap_or((item, ""))
+                    .0
+                    .to_string()
+            } else {
+                item.to_string()
+            };
+            if output_with_source {
+                // lines past the inputs were written to the FIFO
+                let source = input_name(&inputs, i + header_lines).or(append_fifo.as_deref());
+                format!("{}\t{output}", source.unwrap_or_default())
+            } else {
+                output
+            }
+        })
+    };
+    let terminator = record_separator
+        .clone()
+        .unwrap_or_else(|| String::from("\n"));
+    let output: OutputFn = {
+        let output_line = output_line.clone();
+        let terminator = terminator.clone();
+        let chunk = args.chunk;
+        let chunk_separator = args.chunk_separator.clone();
+        let chunk_files = args.chunk_files.clone();
+        let exec_chunked = args.exec_chunked.clone();
+        Box::new(move |selection| {
+            let outputs: Vec<String> = selection.iter().map(|&i| output_line(i)).collect();
+            let texts = chunk_texts(&outputs, chunk.unwrap_or(outputs.len().max(1)), &terminator);
+            // chunks that are not printed are headed by where they go
+            let heading = |n: usize| match (&chunk_files, &exec_chunked) {
+                (Some(prefix), _) => Some(format!("==> {prefix}{} <==\n", n + 1)),
+                (_, Some(command)) => Some(format!(
+                    "==> chunk {}/{} run with {command} <==\n",
+                    n + 1,
+                    texts.len()
+                )),
+                _ => None,
+            };
+            if heading(0).is_some() {
+                texts
+                    .iter()
+                    .enumerate()
+                    .map(|(n, text)| format!("{}{text}", heading(n).unwrap_or_default()))
+                    .collect()
+            } else {
+                texts.join(&format!("{chunk_separator}{terminator}"))
+            }
+        })
+    };
 
-    let selector_content = prepare_selector_content(&input_stream, args.numbering, args.id_mode);
+    let options = SelectorOptions {
+        preview: args.preview.map(|p| Template::new(&p, !args.no_shell)),
+        order,
+        shuffle: args.shuffle,
+        sample: args.sample,
+        browse: args.browse,
+        wrap: args.wrap,
+        tree,
+        select_descendants: args.select_descendants,
+        disabled,
+        skip_disabled: args.skip_disabled,
+        header,
+        header_line: !args.no_header,
+        hint: !args.no_hint,
+        groups,
+        detail: args.detail,
+        index: args.index,
+        scrollbar: args.scrollbar,
+        scroll_off: args.scroll_off,
+        no_cycle: args.no_cycle || config.no_cycle,
+        reverse: args.reverse,
+        inline: args.inline,
+        height: args.height,
+        record: args.record.clone(),
+        replay,
+        selected,
+        max_select: args.max_select,
+        min_select: args.min_select,
+        keymap,
+        gutter,
+        colors,
+        color_depth,
+        ls_colors: args.ls_colors.then(LsColors::from_env),
+        paths: args.paths,
+        listing,
+        diff: sides,
+        cursor,
+        warning,
+        sum: args
+            .sum
+            .map(|field| FieldValues::read(items.as_ref(), &delimiter, field)),
+        budget: args
+            .budget
+            .map(|spec| Budget::new(&spec, items.as_ref(), &delimiter)),
+        confirm_abort: args.confirm_abort,
+        case_mode: if args.ignore_case {
+            CaseMode::Ignore
+        } else if args.case_sensitive {
+            CaseMode::Sensitive
+        } else {
+            CaseMode::Smart
+        },
+        match_mode: args.match_mode,
+        tiebreak: args.tiebreak,
+        #[cfg(feature = "lua")]
+        script,
+        filter_cmd: args.filter_cmd.map(|f| Template::new(&f, !args.no_shell)),
+        command_timeout: args.command_timeout,
+        append_fifo: args.append_fifo.clone(),
+        appender,
+        selection_order: args.selection_order,
+        expect: args.expect.clone(),
+        exec: args.exec.clone(),
+        menu,
+        output: Some(output),
+    };
 
-    let Ok(selected_indices) = tui_selector::select(selector_content) else {
+    require_tty();
+    let Ok(outcome) = tui_selector::select(entries, options) else {
         eprintln!("tui_selector: error: unable to access tty i/o.");
         exit(1);
     };
 
-    if let Some(selection) = selected_indices {
-        for i in selection {
-            let mut item: &str = &input_stream[i];
-            if args.id_mode {
-                item = item.split_once("::").unwrap_or((item, "")).0;
+    if !args.source.is_empty() || !args.file.is_empty() {
+        if let Ok(dir) = env::current_dir() {
+            let invocation = Invocation {
+                dir: dir.to_string_lossy().into_owned(),
+                args: env::args().skip(1).collect(),
+            };
+            // failing to record only leaves the invocation out of `tui_selector recent`
+            let _ = history::record(&invocation);
+        }
+    }
+
+    if args.stats {
+        print_stats(items.len(), &outcome, start);
+    }
+    if let Some(path) = &args.dump_state {
+        let entry = |index: usize| {
+            let line = items.get(index).into_owned();
+            let id = id_delimiter.map(|d| d.split_once(&line).unwrap_or((&line, "")).0.to_string());
+            state::Entry::new(index, id, line)
+        };
+        let state = State {
+            aborted: outcome.aborted,
+            key: outcome.key.map(key_name),
+            query: outcome.query.clone(),
+            cursor: outcome.cursor.map(entry),
+            total: items.len(),
+            matched: outcome.matched,
+            selected: outcome.selected.iter().map(|&i| entry(i)).collect(),
+        };
+        if let Err(e) = state.dump(path) {
+            eprintln!("tui_selector: error: unable to write state to '{path}': {e}");
+            exit(1);
+        }
+    }
+    if !args.expect.is_empty() && !outcome.aborted {
+        let key = outcome
+            .key
+            .filter(|key| args.expect.contains(key))
+            .map(key_name);
+        print!("{}{terminator}", key.unwrap_or_default());
+    }
+
+    let Some(selection) = outcome.selection else {
+        return;
+    };
+    let outputs: Vec<String> = selection.into_iter().map(|i| output_line(i)).collect();
+    let chunk_size = args.chunk.unwrap_or(outputs.len().max(1));
+    if let Some(command) = &args.exec {
+        run_each(&Template::new(command, !args.no_shell), &outputs, args.jobs);
+    }
+    if let Some(command) = &args.exec_chunked {
+        run_chunked(
+            &Template::new(command, !args.no_shell),
+            &outputs,
+            chunk_size,
+        );
+    }
+    for (n, text) in chunk_texts(&outputs, chunk_size, &terminator)
+        .into_iter()
+        .enumerate()
+    {
+        if let Some(prefix) = &args.chunk_files {
+            let path = format!("{prefix}{}", n + 1);
+            if let Err(e) = fs::write(&path, text) {
+                eprintln!("tui_selector: error: unable to write '{path}': {e}");
+                exit(1);
+            }
+        } else {
+            if n > 0 {
+                print!("{}{terminator}", args.chunk_separator);
             }
-            println!("{item}");
+            print!("{text}");
         }
     }
 }
@@ -0,0 +1,262 @@
+use std::ops::Range;
+
+use regex::{Regex, RegexBuilder};
+use unicode_width::UnicodeWidthStr;
+
+/// Case sensitivity of the patterns typed in the selector.
+#[derive(Clone, Copy, Default)]
+pub enum CaseMode {
+    /// Case-insensitive unless the pattern has an uppercase character.
+    #[default]
+    Smart,
+    Ignore,
+    Sensitive,
+}
+
+impl CaseMode {
+    /// Returns true if matching the provided pattern ignores case.
+    pub fn ignores_case(self, pattern: &str) -> bool {
+        match self {
+            CaseMode::Smart => !pattern.chars().any(char::is_uppercase),
+            CaseMode::Ignore => true,
+            CaseMode::Sensitive => false,
+        }
+    }
+}
+
+/// Returns the compiled regular expression, case-insensitive according to the case mode.
+pub fn build_regex(pattern: &str, case: CaseMode) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(case.ignores_case(pattern))
+        .build()
+}
+
+/// Algorithm used to match the query typed in the search and filter prompts.
+#[derive(Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum MatchMode {
+    /// Entries equal to the query
+    Exact,
+    /// Entries containing the query
+    Substring,
+    /// Entries containing the query characters in order, ranked by how close they are
+    Fuzzy,
+    /// Entries matching the query as a regular expression (literal while it is not valid)
+    #[default]
+    Regex,
+}
+
+impl MatchMode {
+    /// Returns the mode following this one, for cycling through modes at runtime.
+    pub fn next(self) -> MatchMode {
+        match self {
+            MatchMode::Exact => MatchMode::Substring,
+            MatchMode::Substring => MatchMode::Fuzzy,
+            MatchMode::Fuzzy => MatchMode::Regex,
+            MatchMode::Regex => MatchMode::Exact,
+        }
+    }
+
+    /// Returns the name of the mode as shown in the selector.
+    pub fn name(self) -> &'static str {
+        match self {
+            MatchMode::Exact => "exact",
+            MatchMode::Substring => "substring",
+            MatchMode::Fuzzy => "fuzzy",
+            MatchMode::Regex => "regex",
+        }
+    }
+
+    /// Returns the matcher for the provided query using this mode.
+    pub fn matcher(self, query: &str, case: CaseMode) -> Box<dyn Matcher> {
+        let ignore_case = case.ignores_case(query);
+        match self {
+            MatchMode::Exact => Box::new(ExactMatcher::new(query, ignore_case)),
+            MatchMode::Substring => Box::new(SubstringMatcher::new(query, ignore_case)),
+            MatchMode::Fuzzy => Box::new(FuzzyMatcher::new(query, ignore_case)),
+            MatchMode::Regex => {
+                // incomplete patterns are matched literally while typing
+                let regex = build_regex(query, case)
+                    .or_else(|_| build_regex(&regex::escape(query), case))
+                    .unwrap_or_else(|_| Regex::new("$^").expect("valid regex"));
+                Box::new(RegexMatcher(regex))
+            }
+        }
+    }
+}
+
+/// Criterion ordering filtered entries, applied in the order given to break ties.
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum Tiebreak {
+    /// Better match score first
+    Score,
+    /// Earlier in the input first
+    Index,
+    /// Shorter entries first
+    Length,
+    /// Match closer to the start first
+    Begin,
+    /// Match closer to the end first
+    End,
+}
+
+impl Tiebreak {
+    /// Returns the sort key (lower first) of the entry at the provided index, whose displayed
+    /// text has the provided match score.
+    pub fn key(self, idx: usize, text: &str, score: i64, matcher: &dyn Matcher) -> i64 {
+        let ranges = || matcher.find(text).unwrap_or_default();
+        match self {
+            Tiebreak::Score => -score,
+            Tiebreak::Index => idx as i64,
+            Tiebreak::Length => text.width() as i64,
+            Tiebreak::Begin => ranges().first().map_or(0, |r| r.start) as i64,
+            Tiebreak::End => (text.len() - ranges().last().map_or(text.len(), |r| r.end)) as i64,
+        }
+    }
+}
+
+/// Query compiled for matching entries.
+pub trait Matcher {
+    /// Returns the byte ranges of the text matched by the query, `None` if it does not match.
+    fn find(&self, text: &str) -> Option<Vec<Range<usize>>>;
+
+    /// Returns how well the text matches the query (higher is better), `None` if it does not
+    /// match. Matchers without ranking score every match the same.
+    fn score(&self, text: &str) -> Option<i64> {
+        self.find(text).map(|_| 0)
+    }
+}
+
+/// Returns true if both characters are equal, ignoring case if required.
+fn chars_eq(a: char, b: char, ignore_case: bool) -> bool {
+    a == b || (ignore_case && a.to_lowercase().eq(b.to_lowercase()))
+}
+
+/// Returns the end of the query matched at the start of the text, `None` if it does not match.
+fn match_at(text: &str, query: &[char], ignore_case: bool) -> Option<usize> {
+    let mut chars = text.char_indices();
+    for &q in query {
+        let (_, c) = chars.next()?;
+        if !chars_eq(c, q, ignore_case) {
+            return None;
+        }
+    }
+    Some(chars.next().map_or(text.len(), |(i, _)| i))
+}
+
+/// Matches entries equal to the query.
+struct ExactMatcher {
+    query: Vec<char>,
+    ignore_case: bool,
+}
+
+impl ExactMatcher {
+    fn new(query: &str, ignore_case: bool) -> ExactMatcher {
+        ExactMatcher {
+            query: query.chars().collect(),
+            ignore_case,
+        }
+    }
+}
+
+impl Matcher for ExactMatcher {
+    fn find(&self, text: &str) -> Option<Vec<Range<usize>>> {
+        match match_at(text, &self.query, self.ignore_case) {
+            Some(end) if end == text.len() => Some(std::iter::once(0..end).collect()),
+            _ => None,
+        }
+    }
+}
+
+/// Matches entries containing the query.
+struct SubstringMatcher {
+    query: Vec<char>,
+    ignore_case: bool,
+}
+
+impl SubstringMatcher {
+    fn new(query: &str, ignore_case: bool) -> SubstringMatcher {
+        SubstringMatcher {
+            query: query.chars().collect(),
+            ignore_case,
+        }
+    }
+}
+
+impl Matcher for SubstringMatcher {
+    fn find(&self, text: &str) -> Option<Vec<Range<usize>>> {
+        let mut ranges = Vec::new();
+        let mut start = 0;
+        while start <= text.len() {
+            match match_at(&text[start..], &self.query, self.ignore_case) {
+                Some(len) if len > 0 => {
+                    ranges.push(start..start + len);
+                    start += len;
+                }
+                Some(_) => return Some(ranges),
+                None => start += text[start..].chars().next().map_or(1, char::len_utf8),
+            }
+        }
+        (!ranges.is_empty()).then_some(ranges)
+    }
+}
+
+/// Matches entries containing the query characters in order, possibly with gaps.
+struct FuzzyMatcher {
+    query: Vec<char>,
+    ignore_case: bool,
+}
+
+impl FuzzyMatcher {
+    fn new(query: &str, ignore_case: bool) -> FuzzyMatcher {
+        FuzzyMatcher {
+            query: query.chars().collect(),
+            ignore_case,
+        }
+    }
+}
+
+impl Matcher for FuzzyMatcher {
+    fn find(&self, text: &str) -> Option<Vec<Range<usize>>> {
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        let mut query = self.query.iter().peekable();
+        for (i, c) in text.char_indices() {
+            let Some(&&q) = query.peek() else {
+                break;
+            };
+            if chars_eq(c, q, self.ignore_case) {
+                query.next();
+                match ranges.last_mut() {
+                    Some(last) if last.end == i => last.end = i + c.len_utf8(),
+                    _ => ranges.push(i..i + c.len_utf8()),
+                }
+            }
+        }
+        query.peek().is_none().then_some(ranges)
+    }
+
+    /// Scores fewer and earlier gaps between the matched characters higher.
+    fn score(&self, text: &str) -> Option<i64> {
+        let ranges = self.find(text)?;
+        let start = ranges.first().map_or(0, |r| r.start) as i64;
+        let span = ranges.last().map_or(0, |r| r.end) as i64 - start;
+        Some(-(ranges.len() as i64) * 16 - span - start)
+    }
+}
+
+/// Matches entries with a regular expression.
+struct RegexMatcher(Regex);
+
+impl Matcher for RegexMatcher {
+    fn find(&self, text: &str) -> Option<Vec<Range<usize>>> {
+        let ranges: Vec<Range<usize>> = self
+            .0
+            .find_iter(text)
+            .map(|m| m.range())
+            .filter(|r| !r.is_empty())
+            .collect();
+        if ranges.is_empty() && !self.0.is_match(text) {
+            return None;
+        }
+        Some(ranges)
+    }
+}
@@ -0,0 +1,67 @@
+use std::fs;
+use std::io::{self, Read};
+
+/// Memory taken by each line besides its text: its string and allocation, and its index in
+/// the listed and visible orders.
+const LINE_OVERHEAD: u64 = 72;
+
+/// Returns the memory the lines of the input would take once loaded, given its size.
+pub fn estimate(bytes: u64, lines: u64) -> u64 {
+    bytes + lines * LINE_OVERHEAD
+}
+
+/// Returns the memory available for starting programs without swapping, if known.
+pub fn available() -> Option<u64> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find(|l| l.starts_with("MemAvailable:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+/// Returns the amount of bytes in mebibytes, as shown in messages.
+pub fn format_size(bytes: u64) -> String {
+    format!("{:.1} MiB", bytes as f64 / 1048576.0)
+}
+
+/// Reader stopping as if the input ended once loading what it read would take more memory
+/// than the limit, so that reading fails early instead of the system running out of memory.
+pub struct MemoryGuard<R> {
+    inner: R,
+    limit: u64,
+    bytes: u64,
+    lines: u64,
+    exceeded: bool,
+}
+
+impl<R: Read> MemoryGuard<R> {
+    pub fn new(inner: R, limit: u64) -> MemoryGuard<R> {
+        MemoryGuard {
+            inner,
+            limit,
+            bytes: 0,
+            lines: 0,
+            exceeded: false,
+        }
+    }
+
+    /// Returns whether reading was stopped by the limit, the input being cut.
+    pub fn exceeded(&self) -> bool {
+        self.exceeded
+    }
+}
+
+impl<R: Read> Read for MemoryGuard<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.exceeded {
+            return Ok(0);
+        }
+        let read = self.inner.read(buf)?;
+        self.bytes += read as u64;
+        self.lines += memchr::memchr_iter(b'\n', &buf[..read]).count() as u64;
+        if estimate(self.bytes, self.lines) > self.limit {
+            self.exceeded = true;
+            return Ok(0);
+        }
+        Ok(read)
+    }
+}
@@ -0,0 +1,57 @@
+use crate::command::Template;
+use crate::config::MenuEntry;
+use crate::keymap::{parse_action, Action};
+
+/// What picking an entry of the action menu does.
+pub enum Operation {
+    /// Runs the command for the entry under the cursor, showing its output over the list if
+    /// `show` is set, or only its failure otherwise.
+    Command { template: Template, show: bool },
+    /// Runs the action as if its key was pressed.
+    Action(Action),
+}
+
+/// Entry of the menu of actions opened on the entry under the cursor.
+pub struct MenuItem {
+    pub label: String,
+    /// Key picking the entry while the menu is open, besides moving to it.
+    pub key: Option<char>,
+    pub operation: Operation,
+}
+
+/// Returns the menu declared in the configuration file, each entry having either a command,
+/// run as with `--exec`, or an action, named as with `--bind`.
+pub fn build(entries: Vec<MenuEntry>, use_shell: bool) -> Result<Vec<MenuItem>, String> {
+    let mut menu: Vec<MenuItem> = Vec::new();
+    for entry in entries {
+        if let Some(key) = entry.key {
+            if menu.iter().any(|item| item.key == Some(key)) {
+                return Err(format!("key '{key}' picks several entries"));
+            }
+        }
+        let operation = match (entry.cmd, entry.action) {
+            (Some(cmd), None) => Operation::Command {
+                template: Template::new(&cmd, use_shell),
+                show: entry.show,
+            },
+            (None, Some(name)) if !entry.show => {
+                let action = parse_action(&name)
+                    .ok_or(format!("'{}': unknown action '{name}'", entry.label))?;
+                if action == Action::Menu {
+                    return Err(format!("'{}': the menu cannot open itself", entry.label));
+                }
+                Operation::Action(action)
+            }
+            (None, Some(_)) => {
+                return Err(format!("'{}': show only applies to commands", entry.label))
+            }
+            _ => return Err(format!("'{}': expected either cmd or action", entry.label)),
+        };
+        menu.push(MenuItem {
+            label: entry.label,
+            key: entry.key,
+            operation,
+        });
+    }
+    Ok(menu)
+}
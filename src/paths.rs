@@ -0,0 +1,47 @@
+use std::fs;
+
+/// What an entry that is a file path points to, looked up when it is first drawn.
+pub enum PathInfo {
+    /// Existing file that is not a symlink.
+    File,
+    /// Symlink to an existing file, with its target as stored in the link.
+    Link(String),
+    /// Symlink whose target does not exist.
+    BrokenLink(String),
+    /// Path that does not exist (or cannot be accessed).
+    Missing,
+}
+
+impl PathInfo {
+    /// Looks up the file at the provided path, following symlinks one level to tell whether
+    /// their target exists.
+    pub fn inspect(path: &str) -> PathInfo {
+        let Ok(metadata) = fs::symlink_metadata(path) else {
+            return PathInfo::Missing;
+        };
+        if !metadata.file_type().is_symlink() {
+            return PathInfo::File;
+        }
+        let target = fs::read_link(path)
+            .map(|target| target.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if fs::metadata(path).is_ok() {
+            PathInfo::Link(target)
+        } else {
+            PathInfo::BrokenLink(target)
+        }
+    }
+
+    /// Returns whether selecting the entry would give a path to nothing.
+    pub fn is_dead(&self) -> bool {
+        matches!(self, PathInfo::BrokenLink(_) | PathInfo::Missing)
+    }
+
+    /// Returns the symlink target, `None` if the path is not a symlink.
+    pub fn target(&self) -> Option<&str> {
+        match self {
+            PathInfo::Link(target) | PathInfo::BrokenLink(target) => Some(target),
+            _ => None,
+        }
+    }
+}
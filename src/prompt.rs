@@ -0,0 +1,156 @@
+use termion::event::Key;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Result of handling a key in the prompt.
+pub enum PromptEvent {
+    /// The text or cursor changed.
+    Edited,
+    /// The text was submitted with enter.
+    Submit(String),
+    /// The prompt was closed with esc.
+    Cancel,
+}
+
+/// Single line text input, edited by grapheme clusters so composed characters (e.g., from an
+/// IME) are moved over and deleted as a whole.
+pub struct Prompt {
+    label: String,
+    text: String,
+    cursor: usize,
+}
+
+impl Prompt {
+    /// Create new empty `Prompt` displayed after the provided label.
+    pub fn new(label: &str) -> Prompt {
+        Prompt {
+            label: label.to_string(),
+            text: String::new(),
+            cursor: 0,
+        }
+    }
+
+    /// Create new `Prompt` displayed after the provided label, with the provided text typed.
+    pub fn with_text(label: &str, text: &str) -> Prompt {
+        Prompt {
+            label: label.to_string(),
+            text: text.to_string(),
+            cursor: text.len(),
+        }
+    }
+
+    /// Returns the text typed so far.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Edits the text according to the provided key.
+    pub fn handle_key(&mut self, key: Key) -> PromptEvent {
+        match key {
+            Key::Char('\n') => return PromptEvent::Submit(self.text.clone()),
+            Key::Esc | Key::Ctrl('c' | 'g') => return PromptEvent::Cancel,
+            Key::Char(c) => {
+                self.text.insert(self.cursor, c);
+                self.cursor += c.len_utf8();
+            }
+            Key::Backspace | Key::Ctrl('h') => {
+                if let Some(start) = self.prev_boundary() {
+                    self.text.replace_range(start..self.cursor, "");
+                    self.cursor = start;
+                }
+            }
+            Key::Delete | Key::Ctrl('d') => {
+                if let Some(end) = self.next_boundary() {
+                    self.text.replace_range(self.cursor..end, "");
+                }
+            }
+            Key::Left | Key::Ctrl('b') => self.cursor = self.prev_boundary().unwrap_or(self.cursor),
+            Key::Right | Key::Ctrl('f') => {
+                self.cursor = self.next_boundary().unwrap_or(self.cursor)
+            }
+            Key::Home | Key::Ctrl('a') => self.cursor = 0,
+            Key::End | Key::Ctrl('e') => self.cursor = self.text.len(),
+            Key::Ctrl('u') => {
+                self.text.replace_range(..self.cursor, "");
+                self.cursor = 0;
+            }
+            _ => {}
+        }
+        PromptEvent::Edited
+    }
+
+    /// Returns the line showing the label and text, along with the display column (0-based)
+    /// of the cursor in it.
+    pub fn render(&self) -> (String, usize) {
+        let line = format!("{}{}", self.label, self.text);
+        let column = self.label.width() + self.text[..self.cursor].width();
+        (line, column)
+    }
+
+    /// Returns the byte offset of the grapheme cluster before the cursor.
+    fn prev_boundary(&self) -> Option<usize> {
+        self.text[..self.cursor]
+            .grapheme_indices(true)
+            .next_back()
+            .map(|(i, _)| i)
+    }
+
+    /// Returns the byte offset of the end of the grapheme cluster after the cursor.
+    fn next_boundary(&self) -> Option<usize> {
+        self.text[self.cursor..]
+            .graphemes(true)
+            .next()
+            .map(|g| self.cursor + g.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(text: &str) -> Prompt {
+        let mut prompt = Prompt::new("/");
+        for c in text.chars() {
+            prompt.handle_key(Key::Char(c));
+        }
+        prompt
+    }
+
+    #[test]
+    fn backspace_deletes_a_combining_cluster() {
+        // "e" followed by a combining acute accent
+        let mut prompt = typed("cafe\u{301}");
+        prompt.handle_key(Key::Backspace);
+        assert_eq!(prompt.text, "caf");
+    }
+
+    #[test]
+    fn backspace_deletes_a_zwj_sequence() {
+        // man, woman and girl joined by zero width joiners
+        let mut prompt = typed("a\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}");
+        prompt.handle_key(Key::Backspace);
+        assert_eq!(prompt.text, "a");
+    }
+
+    #[test]
+    fn delete_and_moves_go_over_whole_clusters() {
+        let mut prompt = typed("e\u{301}x");
+        prompt.handle_key(Key::Home);
+        prompt.handle_key(Key::Delete);
+        assert_eq!(prompt.text, "x");
+        let mut prompt = typed("e\u{301}x");
+        prompt.handle_key(Key::Left);
+        prompt.handle_key(Key::Left);
+        assert_eq!(prompt.render().1, 1);
+    }
+
+    #[test]
+    fn cursor_column_counts_wide_characters() {
+        let mut prompt = typed("日本語");
+        assert_eq!(prompt.render(), (String::from("/日本語"), 7));
+        prompt.handle_key(Key::Left);
+        assert_eq!(prompt.render().1, 5);
+        prompt.handle_key(Key::Backspace);
+        assert_eq!(prompt.render(), (String::from("/日語"), 3));
+    }
+}
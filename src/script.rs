@@ -0,0 +1,102 @@
+use std::error::Error;
+use std::fs;
+
+use mlua::{Function, Lua, Table};
+use termion::event::Key;
+
+use crate::keymap::parse_key;
+
+/// Name of the registry table holding the actions bound by the script, as `{key, function}`.
+const BINDINGS: &str = "tui_selector_bindings";
+
+/// Lua script customizing the selector through hooks, global functions it may define:
+/// - `on_load(count)`: called once the list is read.
+/// - `transform_item(line, number)`: returns the line to display instead of the read one.
+/// - `on_toggle(line, selected)`: called when an entry is toggled.
+///
+/// Custom actions are bound with `bind(key, function(line, selected_lines))`, where the key is
+/// written as in the header hint (e.g., "x" or "ctrl-x"); a returned string is shown in the
+/// header.
+pub struct Script {
+    lua: Lua,
+    keys: Vec<Key>,
+}
+
+impl Script {
+    /// Run the script at the provided path, collecting the actions it binds.
+    pub fn load(path: &str) -> Result<Script, Box<dyn Error>> {
+        let source = fs::read_to_string(path)?;
+        let lua = Lua::new();
+        lua.set_named_registry_value(BINDINGS, lua.create_table()?)?;
+        let bind = lua.create_function(|lua, (key, action): (String, Function)| {
+            let bindings: Table = lua.named_registry_value(BINDINGS)?;
+            let binding = lua.create_table()?;
+            binding.push(key)?;
+            binding.push(action)?;
+            bindings.push(binding)
+        })?;
+        lua.globals().set("bind", bind)?;
+        lua.load(&source).set_name(path).exec()?;
+
+        let mut keys = Vec::new();
+        let bindings: Table = lua.named_registry_value(BINDINGS)?;
+        for binding in bindings.sequence_values::<Table>() {
+            let name: String = binding?.get(1)?;
+            keys.push(parse_key(&name).ok_or(format!("unknown key '{name}'"))?);
+        }
+        Ok(Script { lua, keys })
+    }
+
+    /// Returns the keys of the custom actions, in the order they were bound.
+    pub fn action_keys(&self) -> &[Key] {
+        &self.keys
+    }
+
+    /// Calls the `on_load` hook, if defined.
+    pub fn on_load(&self, count: usize) -> mlua::Result<()> {
+        match self.hook("on_load")? {
+            Some(hook) => hook.call(count),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the line transformed by the `transform_item` hook, the line itself if the hook
+    /// is not defined or fails.
+    pub fn transform_item(&self, idx: usize, line: &str) -> String {
+        let transformed = self.hook("transform_item").and_then(|hook| {
+            hook.map(|h| h.call::<_, String>((line, idx + 1)))
+                .transpose()
+        });
+        match transformed {
+            Ok(Some(line)) => line,
+            _ => line.to_string(),
+        }
+    }
+
+    /// Calls the `on_toggle` hook, if defined.
+    pub fn on_toggle(&self, line: &str, selected: bool) -> mlua::Result<()> {
+        match self.hook("on_toggle")? {
+            Some(hook) => hook.call((line, selected)),
+            None => Ok(()),
+        }
+    }
+
+    /// Runs the custom action at the provided position of `action_keys()`, returning the
+    /// message it produced.
+    pub fn run_action(
+        &self,
+        action: usize,
+        line: &str,
+        selected: Vec<String>,
+    ) -> mlua::Result<Option<String>> {
+        let bindings: Table = self.lua.named_registry_value(BINDINGS)?;
+        let binding: Table = bindings.get(action + 1)?;
+        let function: Function = binding.get(2)?;
+        function.call((line, selected))
+    }
+
+    /// Returns the global function with the provided name, if defined.
+    fn hook(&self, name: &str) -> mlua::Result<Option<Function<'_>>> {
+        self.lua.globals().get(name)
+    }
+}
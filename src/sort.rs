@@ -0,0 +1,205 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::iter::Peekable;
+use std::ptr;
+use std::str::Chars;
+
+use unicode_width::UnicodeWidthStr;
+
+/// Order in which the entries are listed in the selector.
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum SortMode {
+    /// Alphabetical order
+    Alpha,
+    /// Alias of alpha with --collation natural: ignoring case, with numbers in the text
+    /// compared by value (e.g., "img2" before "img10")
+    Natural,
+    /// Leading numbers in increasing order, lines without one last
+    Numeric,
+    /// Shortest lines first
+    Length,
+    /// Input order
+    None,
+}
+
+/// Rules comparing the entries in alphabetical order.
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Collation {
+    /// Rules of the locale set by LC_ALL, LC_COLLATE or LANG
+    Locale,
+    /// Order of the bytes of the text
+    Bytes,
+    /// Order of the text lowercased
+    IgnoreCase,
+    /// Ignoring case, with numbers in the text compared by value (e.g., "file2" before "file10")
+    Natural,
+}
+
+/// Order in which the selected entries are printed.
+#[derive(Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum SelectionOrder {
+    /// Order in which they were selected, "select all" adding entries in the listed order
+    #[default]
+    Selected,
+    /// Order of the input lines
+    Input,
+    /// Order in which they are listed, after sorting and moving entries
+    Listed,
+}
+
+impl SelectionOrder {
+    /// Puts the indices of the selected entries, in the order they were selected, in this
+    /// order, given the indices of the entries in the order they are listed.
+    pub fn arrange(self, selection: &mut [usize], listed: &[usize]) {
+        match self {
+            SelectionOrder::Selected => {}
+            SelectionOrder::Input => selection.sort_unstable(),
+            SelectionOrder::Listed => {
+                let positions: HashMap<usize, usize> = listed
+                    .iter()
+                    .enumerate()
+                    .map(|(pos, &idx)| (idx, pos))
+                    .collect();
+                selection.sort_by_key(|idx| positions.get(idx).copied().unwrap_or(usize::MAX));
+            }
+        }
+    }
+}
+
+/// Returns the indices of the `len` entries sorted by the keys returned for each of them,
+/// keeping the input order of equal keys.
+pub fn sort_order(
+    len: usize,
+    key: impl Fn(usize) -> String,
+    mode: SortMode,
+    collation: Collation,
+    reverse: bool,
+) -> Vec<usize> {
+    // natural order is alphabetical order with the natural collation
+    let (mode, collation) = match mode {
+        SortMode::Natural => (SortMode::Alpha, Collation::Natural),
+        _ => (mode, collation),
+    };
+    let mut order: Vec<usize> = (0..len).collect();
+    if mode != SortMode::None {
+        let keys: Vec<String> = order.iter().map(|&idx| key(idx)).collect();
+        // the locale rules are applied once to each key, the results being compared as bytes
+        let locale_keys: Option<Vec<Vec<u8>>> =
+            (mode == SortMode::Alpha && collation == Collation::Locale).then(|| {
+                use_collation_locale();
+                keys.iter().map(|k| locale_key(k)).collect()
+            });
+        order.sort_by(|&a, &b| {
+            let ordering = match &locale_keys {
+                Some(locale_keys) => locale_keys[a].cmp(&locale_keys[b]),
+                None => compare(&keys[a], &keys[b], mode, collation),
+            };
+            if reverse {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+    } else if reverse {
+        order.reverse();
+    }
+    order
+}
+
+/// Returns the ordering of both keys according to the sort mode, and to the collation in
+/// alphabetical order (the locale one being applied beforehand).
+fn compare(a: &str, b: &str, mode: SortMode, collation: Collation) -> Ordering {
+    match mode {
+        SortMode::Alpha | SortMode::Natural => match collation {
+            Collation::Locale | Collation::Bytes => a.cmp(b),
+            Collation::IgnoreCase => lowercase(a).cmp(lowercase(b)),
+            Collation::Natural => natural_cmp(a, b),
+        },
+        SortMode::Numeric => match (leading_number(a), leading_number(b)) {
+            (Some(x), Some(y)) => x.total_cmp(&y),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        },
+        SortMode::Length => a.width().cmp(&b.width()),
+        SortMode::None => Ordering::Equal,
+    }
+}
+
+/// Returns the characters of the text lowercased.
+fn lowercase(text: &str) -> impl Iterator<Item = char> + '_ {
+    text.chars().flat_map(char::to_lowercase)
+}
+
+/// Returns the ordering of both texts ignoring case, their runs of digits being compared by
+/// value (e.g., "v1.9" before "v1.10"), leading zeros aside.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        let ordering = match (a.peek(), b.peek()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (x, y) = (digit_run(&mut a), digit_run(&mut b));
+                let (x, y) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                x.len().cmp(&y.len()).then_with(|| x.cmp(y))
+            }
+            (Some(&x), Some(&y)) => {
+                a.next();
+                b.next();
+                x.to_lowercase().cmp(y.to_lowercase())
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+/// Consumes the digits at the start of the characters, returning them.
+fn digit_run(chars: &mut Peekable<Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+        digits.push(digit);
+    }
+    digits
+}
+
+/// Sets the collation rules to those of the locale of the environment, the C library using
+/// the "C" locale, i.e., byte order, until told otherwise.
+fn use_collation_locale() {
+    // SAFETY: the string is NUL-terminated, and the other threads do not use the locale
+    unsafe { libc::setlocale(libc::LC_COLLATE, c"".as_ptr()) };
+}
+
+/// Returns the key of the text whose byte order is its order in the collation locale, NUL
+/// characters being left out.
+fn locale_key(text: &str) -> Vec<u8> {
+    let text = CString::new(text.replace('\0', "")).unwrap_or_default();
+    // SAFETY: strxfrm only measures the transformed key with a null buffer of size 0
+    let len = unsafe { libc::strxfrm(ptr::null_mut(), text.as_ptr(), 0) };
+    let mut key = vec![0u8; len + 1];
+    // SAFETY: the buffer has room for the key and its terminating NUL
+    unsafe { libc::strxfrm(key.as_mut_ptr().cast(), text.as_ptr(), key.len()) };
+    key.truncate(len);
+    key
+}
+
+/// Returns the number the text starts with, ignoring leading whitespace.
+fn leading_number(text: &str) -> Option<f64> {
+    let text = text.trim_start();
+    let mut end = 0;
+    let mut seen_dot = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            '-' | '+' if i == 0 => {}
+            '.' if !seen_dot => seen_dot = true,
+            '0'..='9' => {}
+            _ => break,
+        }
+        end = i + 1;
+    }
+    text[..end].parse().ok()
+}
@@ -0,0 +1,125 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::items::Items;
+
+/// FNV-1a parameters, a hash that is the same on every machine and version.
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// Final state of the selector, written as JSON with `--dump-state` for wrapper scripts and
+/// read back with `--resume`.
+#[derive(Serialize, Deserialize)]
+pub struct State {
+    /// Whether the selector was quit (or interrupted) instead of accepting a selection.
+    pub aborted: bool,
+    /// Name of the key that closed the selector (e.g., "enter"), `null` if its input ended.
+    pub key: Option<String>,
+    /// Filter (or search) query typed when closing, `null` without either.
+    pub query: Option<String>,
+    /// Entry under the cursor, `null` if no entry was listed.
+    pub cursor: Option<Entry>,
+    /// Amount of entries listed.
+    pub total: usize,
+    /// Amount of entries matching the query (all without one).
+    pub matched: usize,
+    /// Entries selected when closing, also if the selection was discarded.
+    pub selected: Vec<Entry>,
+}
+
+/// Entry referred to in the state.
+#[derive(Serialize, Deserialize)]
+pub struct Entry {
+    /// Position of the entry in the input list, from 0.
+    pub index: usize,
+    /// ID of the entry in ID mode, `null` otherwise.
+    pub id: Option<String>,
+    /// Input line (or record) of the entry.
+    pub line: String,
+    /// Hash of the line, finding the entry again in the same input listed in another order
+    /// (computed from the line if missing).
+    #[serde(default)]
+    pub hash: String,
+}
+
+impl Entry {
+    /// Returns the entry at the provided index, with the line's hash.
+    pub fn new(index: usize, id: Option<String>, line: String) -> Entry {
+        let hash = line_hash(&line);
+        Entry {
+            index,
+            id,
+            line,
+            hash,
+        }
+    }
+
+    /// Returns the index of the entry among the indices of the items by hash, skipping those
+    /// already found and adding it to them.
+    fn find(
+        &self,
+        by_hash: &HashMap<String, Vec<usize>>,
+        found: &mut HashSet<usize>,
+    ) -> Option<usize> {
+        let hash = if self.hash.is_empty() {
+            line_hash(&self.line)
+        } else {
+            self.hash.clone()
+        };
+        let candidates = by_hash.get(&hash)?;
+        if candidates.contains(&self.index) && found.insert(self.index) {
+            return Some(self.index);
+        }
+        candidates.iter().copied().find(|&idx| found.insert(idx))
+    }
+}
+
+impl State {
+    /// Writes the state to the file at the provided path, replacing its content.
+    pub fn dump(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)? + "\n")?;
+        Ok(())
+    }
+
+    /// Reads the state written to the file at the provided path.
+    pub fn load(path: &str) -> Result<State, Box<dyn Error>> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Returns the indices of the selected entries and of the entry under the cursor among the
+    /// provided items, `None` for those no longer listed. Entries are found by the hash of
+    /// their line, at the same index if it still has it, otherwise at the first index with
+    /// that hash not found yet, so duplicate lines are told apart.
+    pub fn locate(&self, items: &dyn Items) -> (Vec<Option<usize>>, Option<usize>) {
+        let mut by_hash: HashMap<String, Vec<usize>> = HashMap::new();
+        for idx in 0..items.len() {
+            by_hash
+                .entry(line_hash(&items.get(idx)))
+                .or_default()
+                .push(idx);
+        }
+        let mut found = HashSet::new();
+        let selected = self
+            .selected
+            .iter()
+            .map(|entry| entry.find(&by_hash, &mut found))
+            .collect();
+        // the cursor may be on a selected entry, which it is found at again
+        let cursor = self
+            .cursor
+            .as_ref()
+            .and_then(|entry| entry.find(&by_hash, &mut HashSet::new()));
+        (selected, cursor)
+    }
+}
+
+/// Returns the hash of the line as 16 hexadecimal digits.
+fn line_hash(line: &str) -> String {
+    let hash = line.bytes().fold(FNV_OFFSET, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    });
+    format!("{hash:016x}")
+}
@@ -0,0 +1,55 @@
+use unicode_width::UnicodeWidthStr;
+
+use crate::ansi;
+use crate::delimiter::Delimiter;
+
+/// Maximum width of a column, longer cells are cut.
+const MAX_COLUMN_WIDTH: usize = 40;
+
+/// Text separating the columns.
+const COLUMN_GAP: &str = "  ";
+
+/// Widths of the columns of delimited lines, for displaying them aligned.
+pub struct Table {
+    widths: Vec<usize>,
+    delimiter: Delimiter,
+}
+
+impl Table {
+    /// Computes the width of each column as that of its widest cell in the provided lines,
+    /// up to `MAX_COLUMN_WIDTH`.
+    pub fn new<S: AsRef<str>>(lines: &[S], delimiter: Delimiter) -> Table {
+        let mut widths: Vec<usize> = Vec::new();
+        for line in lines {
+            for (i, cell) in delimiter.split(line.as_ref()).iter().enumerate() {
+                let width = cell.trim().width().min(MAX_COLUMN_WIDTH);
+                match widths.get_mut(i) {
+                    Some(w) => *w = (*w).max(width),
+                    None => widths.push(width),
+                }
+            }
+        }
+        Table { widths, delimiter }
+    }
+
+    /// Returns the line with its cells padded to the width of their column (cut with "…" if
+    /// wider) and separated by two spaces, the last cell being left as is.
+    pub fn format(&self, line: &str) -> String {
+        let cells = self.delimiter.split(line);
+        let last = cells.len().saturating_sub(1);
+        let mut formatted = String::with_capacity(line.len());
+        for (i, cell) in cells.iter().enumerate() {
+            let cell = cell.trim();
+            if i == last {
+                formatted.push_str(cell);
+                break;
+            }
+            let width = self.widths.get(i).copied().unwrap_or(MAX_COLUMN_WIDTH);
+            let cell = ansi::truncate(cell, width);
+            formatted.push_str(&cell);
+            formatted.push_str(&" ".repeat(width.saturating_sub(cell.width())));
+            formatted.push_str(COLUMN_GAP);
+        }
+        formatted
+    }
+}
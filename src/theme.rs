@@ -0,0 +1,413 @@
+use std::env;
+
+use termion::color::{AnsiValue, Bg, Fg, Reset, Rgb};
+
+/// Escape sequence querying the terminal background color (OSC 11).
+pub const BACKGROUND_QUERY: &str = "\x1b]11;?\x07";
+
+/// Escape sequence querying the cursor shape (DECRQSS for DECSCUSR).
+pub const CURSOR_SHAPE_QUERY: &str = "\x1bP$q q\x1b\\";
+
+/// Cursor shape set by the user, as numbered by DECSCUSR (e.g., 5 for a blinking bar).
+#[derive(Clone, Copy, PartialEq)]
+pub struct CursorShape(pub u8);
+
+impl CursorShape {
+    /// Parses the reply to `CURSOR_SHAPE_QUERY` ("1$rN q", without the DCS prefix and
+    /// terminator), returns `None` if the terminal does not report the shape.
+    pub fn from_dcs_reply(reply: &str) -> Option<CursorShape> {
+        let shape = reply.strip_prefix("1$r")?.strip_suffix(" q")?;
+        shape.parse().ok().map(CursorShape)
+    }
+
+    /// Returns the escape sequence setting the cursor to this shape.
+    pub fn restore(self) -> String {
+        format!("\x1b[{} q", self.0)
+    }
+}
+
+/// Brightness of the terminal background.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Background {
+    Dark,
+    Light,
+}
+
+impl Background {
+    /// Parses the reply to `BACKGROUND_QUERY` ("11;rgb:RRRR/GGGG/BBBB", without the OSC
+    /// prefix and terminator), returns `None` if it is not a valid reply.
+    pub fn from_osc_reply(reply: &str) -> Option<Background> {
+        let rgb = reply.strip_prefix("11;rgb:")?;
+        let channels: Vec<f64> = rgb
+            .split('/')
+            .map(|c| {
+                let max = 16_f64.powi(c.len() as i32) - 1.0;
+                u32::from_str_radix(c, 16).map(|v| f64::from(v) / max)
+            })
+            .collect::<Result<_, _>>()
+            .ok()?;
+        let [r, g, b] = channels[..] else {
+            return None;
+        };
+        let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        Some(if luminance > 0.5 {
+            Background::Light
+        } else {
+            Background::Dark
+        })
+    }
+}
+
+/// Escape sequences setting the colors of each styled element of the selector.
+pub struct Theme {
+    pub header: String,
+    pub selected: String,
+    /// Entry under the cursor, unless it is selected.
+    pub cursor: String,
+    pub warning: String,
+    pub highlight: String,
+    /// Broken symlinks and missing paths with `--paths`.
+    pub missing: String,
+    /// Marks of the lines only in the first or the second list with `--diff-lists`.
+    pub removed: String,
+    pub added: String,
+    /// Sets the default colors back after a styled element.
+    pub reset: String,
+}
+
+impl Theme {
+    /// Returns the default theme for the provided terminal background, with the colors the
+    /// terminal can display (none in monochrome).
+    pub fn for_background(background: Background, depth: ColorDepth) -> Theme {
+        let style = |fg: u8, bg: Option<u8>| {
+            let mut style = color_sequence(Color::Indexed(fg), false, depth);
+            if let Some(bg) = bg {
+                style.push_str(&color_sequence(Color::Indexed(bg), true, depth));
+            }
+            style
+        };
+        let reset = format!(
+            "{}{}",
+            color_sequence(Color::Default, false, depth),
+            color_sequence(Color::Default, true, depth)
+        );
+        match background {
+            Background::Dark => Theme {
+                header: style(BLACK, Some(WHITE)),
+                selected: style(BLACK, Some(WHITE)),
+                cursor: String::new(),
+                warning: style(WHITE, Some(RED)),
+                highlight: style(BRIGHT_RED, None),
+                missing: style(RED, None),
+                removed: style(BRIGHT_RED, None),
+                added: style(BRIGHT_GREEN, None),
+                reset,
+            },
+            Background::Light => Theme {
+                header: style(WHITE, Some(BLUE)),
+                selected: style(WHITE, Some(BLACK)),
+                cursor: String::new(),
+                warning: style(WHITE, Some(RED)),
+                highlight: style(RED, None),
+                missing: style(RED, None),
+                removed: style(RED, None),
+                added: style(GREEN, None),
+                reset,
+            },
+        }
+    }
+
+    /// Sets the colors given by the user, replacing those of the theme, with the closest
+    /// ones the terminal supports.
+    pub fn apply(&mut self, colors: &Colors, depth: ColorDepth) {
+        for color in &colors.0 {
+            let style = match color.element {
+                Element::Header => &mut self.header,
+                Element::Selected => &mut self.selected,
+                Element::Cursor => &mut self.cursor,
+                Element::Warning => &mut self.warning,
+                Element::Highlight => &mut self.highlight,
+                Element::Missing => &mut self.missing,
+                Element::Removed => &mut self.removed,
+                Element::Added => &mut self.added,
+            };
+            // appended after the theme's sequences, so it takes precedence over them
+            style.push_str(&color.sequence(depth));
+        }
+    }
+}
+
+/// Styled element of the selector, as named in `--color`.
+#[derive(Clone, Copy)]
+pub enum Element {
+    Header,
+    Selected,
+    Cursor,
+    Warning,
+    Highlight,
+    Missing,
+    Removed,
+    Added,
+}
+
+/// Names of the 16 basic terminal colors, in the order of their ANSI numbers (the bright
+/// variants being prefixed with "bright-").
+const COLOR_NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+/// Values of the 16 basic colors in xterm, against which other colors are matched on
+/// terminals supporting only those.
+const BASIC_RGB: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// Basic colors of the default themes, by their ANSI number.
+const BLACK: u8 = 0;
+const RED: u8 = 1;
+const GREEN: u8 = 2;
+const BLUE: u8 = 4;
+const WHITE: u8 = 7;
+const BRIGHT_RED: u8 = 9;
+const BRIGHT_GREEN: u8 = 10;
+
+/// Channel levels of the 6×6×6 color cube of the 256-color palette (colors 16–231).
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Amount of colors the terminal can display.
+#[derive(Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum ColorDepth {
+    /// Any RGB color (24-bit)
+    #[value(name = "truecolor")]
+    TrueColor,
+    /// The 256-color palette
+    #[value(name = "256")]
+    Palette,
+    /// The 16 basic colors
+    #[value(name = "16")]
+    Basic,
+    /// No colors, selected entries being marked with "*"
+    #[default]
+    #[value(name = "mono")]
+    Mono,
+}
+
+impl ColorDepth {
+    /// Returns the depth announced by the environment: none if `$NO_COLOR` is set (see
+    /// https://no-color.org), `$COLORTERM` set to "truecolor" or "24bit" for RGB colors, a
+    /// `$TERM` ending with "256color" for the palette, and none with a "dumb" or missing
+    /// `$TERM`.
+    pub fn detect() -> ColorDepth {
+        if env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+            return ColorDepth::Mono;
+        }
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            return ColorDepth::TrueColor;
+        }
+        match env::var("TERM") {
+            Ok(term) if term.ends_with("256color") => ColorDepth::Palette,
+            Ok(term) if term != "dumb" => ColorDepth::Basic,
+            _ => ColorDepth::Mono,
+        }
+    }
+}
+
+/// Terminal color set by the user.
+#[derive(Clone, Copy)]
+pub enum Color {
+    /// Default color of the terminal.
+    Default,
+    /// Color of the 256-color palette, the first 16 being the basic colors.
+    Indexed(u8),
+    /// Color by its red, green and blue values.
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    /// Parses a color name (e.g., "blue", "bright-red" or "default"), palette index (e.g.,
+    /// "208") or hex RGB value (e.g., "#ff8700").
+    fn parse(name: &str) -> Result<Color, String> {
+        if name == "default" {
+            return Ok(Color::Default);
+        }
+        if let Ok(index) = name.parse::<u8>() {
+            return Ok(Color::Indexed(index));
+        }
+        if let Some(hex) = name.strip_prefix('#') {
+            let channel = |i: usize| {
+                hex.get(i..i + 2)
+                    .and_then(|c| u8::from_str_radix(c, 16).ok())
+            };
+            return match (hex.len(), channel(0), channel(2), channel(4)) {
+                (6, Some(r), Some(g), Some(b)) => Ok(Color::Rgb(r, g, b)),
+                _ => Err(format!("invalid color '{name}', expected #RRGGBB")),
+            };
+        }
+        let (base, offset) = match name.strip_prefix("bright-") {
+            Some(base) => (base, 8),
+            None => (name, 0),
+        };
+        let n = COLOR_NAMES
+            .iter()
+            .position(|&c| c == base)
+            .ok_or(format!("unknown color '{name}'"))?;
+        Ok(Color::Indexed(n as u8 + offset))
+    }
+
+    /// Returns the closest color the terminal can display.
+    fn degrade(self, depth: ColorDepth) -> Color {
+        match (self, depth) {
+            (Color::Rgb(r, g, b), ColorDepth::Palette) => {
+                Color::Indexed(nearest_palette((r, g, b)))
+            }
+            (Color::Rgb(r, g, b), ColorDepth::Basic) => Color::Indexed(nearest_basic((r, g, b))),
+            (Color::Indexed(n), ColorDepth::Basic) if n >= 16 => {
+                Color::Indexed(nearest_basic(palette_rgb(n)))
+            }
+            (color, _) => color,
+        }
+    }
+}
+
+/// Returns the RGB value of the palette color.
+fn palette_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => BASIC_RGB[index as usize],
+        16..=231 => {
+            let cube = index - 16;
+            let level = |n: u8| CUBE_LEVELS[n as usize];
+            (level(cube / 36), level(cube / 6 % 6), level(cube % 6))
+        }
+        _ => {
+            let gray = 8 + (index - 232) * 10;
+            (gray, gray, gray)
+        }
+    }
+}
+
+/// Returns the squared distance between two RGB colors.
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let channel = |x: u8, y: u8| (i32::from(x) - i32::from(y)).pow(2) as u32;
+    channel(a.0, b.0) + channel(a.1, b.1) + channel(a.2, b.2)
+}
+
+/// Returns the palette color closest to the RGB value, in the color cube or the gray ramp
+/// (the basic colors vary between terminals).
+fn nearest_palette(rgb: (u8, u8, u8)) -> u8 {
+    (16..=255)
+        .min_by_key(|&i| distance(rgb, palette_rgb(i)))
+        .unwrap_or(16)
+}
+
+/// Returns the basic color closest to the RGB value.
+fn nearest_basic(rgb: (u8, u8, u8)) -> u8 {
+    (0..16)
+        .min_by_key(|&i| distance(rgb, BASIC_RGB[i as usize]))
+        .unwrap_or(0)
+}
+
+/// Color set for the foreground or background of an element.
+#[derive(Clone)]
+pub struct ColorOverride {
+    element: Element,
+    background: bool,
+    color: Color,
+}
+
+impl ColorOverride {
+    /// Returns the escape sequence setting the color, or the closest one the terminal can
+    /// display.
+    fn sequence(&self, depth: ColorDepth) -> String {
+        color_sequence(self.color, self.background, depth)
+    }
+}
+
+/// Returns the escape sequence setting the foreground or background color, or the closest one
+/// the terminal can display (nothing in monochrome). Basic colors use their own codes (e.g.,
+/// "ESC[34m" for blue) instead of the palette ones on terminals without the palette.
+fn color_sequence(color: Color, background: bool, depth: ColorDepth) -> String {
+    if depth == ColorDepth::Mono {
+        return String::new();
+    }
+    match (color.degrade(depth), background) {
+        (Color::Default, false) => Fg(Reset).to_string(),
+        (Color::Default, true) => Bg(Reset).to_string(),
+        (Color::Indexed(n), background) if depth == ColorDepth::Basic => {
+            let base = if background { 40 } else { 30 };
+            let code = if n < 8 { base + n } else { base + 60 + n - 8 };
+            format!("\x1b[{code}m")
+        }
+        (Color::Indexed(n), false) => Fg(AnsiValue(n)).to_string(),
+        (Color::Indexed(n), true) => Bg(AnsiValue(n)).to_string(),
+        (Color::Rgb(r, g, b), false) => Fg(Rgb(r, g, b)).to_string(),
+        (Color::Rgb(r, g, b), true) => Bg(Rgb(r, g, b)).to_string(),
+    }
+}
+
+/// Colors set by the user for the elements of the selector.
+#[derive(Clone, Default)]
+pub struct Colors(pub Vec<ColorOverride>);
+
+/// Returns the colors from their "ELEMENT:fg=COLOR:bg=COLOR,..." argument, e.g.,
+/// "header:bg=blue,selected:fg=black:bg=green".
+pub fn parse_colors(arg: &str) -> Result<Colors, String> {
+    let mut colors = Vec::new();
+    for spec in arg.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let mut parts = spec.split(':');
+        let element = match parts.next().unwrap_or_default() {
+            "header" => Element::Header,
+            "selected" => Element::Selected,
+            "cursor" => Element::Cursor,
+            "warning" => Element::Warning,
+            "highlight" => Element::Highlight,
+            "missing" => Element::Missing,
+            "removed" => Element::Removed,
+            "added" => Element::Added,
+            other => {
+                return Err(format!(
+                    "unknown element '{other}', expected header, selected, cursor, warning, highlight, missing, removed or added"
+                ))
+            }
+        };
+        let mut layers = parts.peekable();
+        if layers.peek().is_none() {
+            return Err(format!(
+                "no color given for '{spec}', expected e.g. '{spec}:fg=blue'"
+            ));
+        }
+        for layer in layers {
+            let background = match layer.split_once('=') {
+                Some(("fg", _)) => false,
+                Some(("bg", _)) => true,
+                _ => {
+                    return Err(format!(
+                        "invalid color '{layer}', expected fg=COLOR or bg=COLOR"
+                    ))
+                }
+            };
+            let color = Color::parse(&layer[3..])?;
+            colors.push(ColorOverride {
+                element,
+                background,
+                color,
+            });
+        }
+    }
+    Ok(Colors(colors))
+}
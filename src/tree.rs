@@ -0,0 +1,103 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+use crate::items::Items;
+
+/// Hierarchy of the input lines, where the parent of a line is the closest line before it
+/// that is less indented or, with a delimiter, whose path is a prefix of its own.
+pub struct Tree {
+    parents: Vec<Option<usize>>,
+    depths: Vec<usize>,
+    has_children: Vec<bool>,
+    /// Delimiter between path components, `None` when indentation defines the hierarchy.
+    delimiter: Option<String>,
+}
+
+impl Tree {
+    /// Builds the hierarchy of the lines, nested by indentation or by the path components
+    /// separated by the delimiter (e.g., "/" for the output of `find`).
+    pub fn new(items: &dyn Items, delimiter: Option<String>) -> Tree {
+        let count = items.len();
+        let mut tree = Tree {
+            parents: Vec::with_capacity(count),
+            depths: Vec::with_capacity(count),
+            has_children: vec![false; count],
+            delimiter,
+        };
+        // ancestors of the current line, from the root
+        let mut stack: Vec<usize> = Vec::new();
+        for idx in 0..count {
+            let line = items.get(idx);
+            while let Some(&top) = stack.last() {
+                if tree.is_parent(&items.get(top), &line) {
+                    break;
+                }
+                stack.pop();
+            }
+            let parent = stack.last().copied();
+            if let Some(parent) = parent {
+                tree.has_children[parent] = true;
+            }
+            tree.parents.push(parent);
+            tree.depths.push(stack.len());
+            stack.push(idx);
+        }
+        tree
+    }
+
+    /// Returns true if the `parent` line can be the parent of the `child` line.
+    fn is_parent(&self, parent: &str, child: &str) -> bool {
+        match &self.delimiter {
+            Some(d) => child
+                .strip_prefix(parent.trim_end_matches(d.as_str()))
+                .is_some_and(|rest| rest.len() > d.len() && rest.starts_with(d.as_str())),
+            None => indentation(parent) < indentation(child),
+        }
+    }
+
+    /// Returns true if the line at the provided index has children.
+    pub fn has_children(&self, idx: usize) -> bool {
+        self.has_children[idx]
+    }
+
+    /// Returns the index of the parent of the line, `None` for top-level lines.
+    pub fn parent(&self, idx: usize) -> Option<usize> {
+        self.parents[idx]
+    }
+
+    /// Returns the indices of the descendants of the line, which follow it in the input.
+    pub fn descendants(&self, idx: usize) -> impl Iterator<Item = usize> + '_ {
+        (idx + 1..self.depths.len()).take_while(move |&i| self.depths[i] > self.depths[idx])
+    }
+
+    /// Returns true if the line is hidden because one of its ancestors is collapsed.
+    pub fn is_hidden(&self, idx: usize, collapsed: &HashSet<usize>) -> bool {
+        let mut ancestor = self.parents[idx];
+        while let Some(a) = ancestor {
+            if collapsed.contains(&a) {
+                return true;
+            }
+            ancestor = self.parents[a];
+        }
+        false
+    }
+
+    /// Returns the line as displayed in the tree: with a delimiter, its last path component
+    /// indented by its depth, otherwise the line itself.
+    pub fn label<'a>(&self, idx: usize, line: &'a str) -> Cow<'a, str> {
+        let Some(d) = &self.delimiter else {
+            return Cow::Borrowed(line);
+        };
+        match line.trim_end_matches(d.as_str()).rsplit(d.as_str()).next() {
+            Some(name) if !name.is_empty() => {
+                Cow::Owned(format!("{}{name}", "  ".repeat(self.depths[idx])))
+            }
+            _ => Cow::Borrowed(line),
+        }
+    }
+}
+
+/// Returns the amount of leading whitespace characters of the line.
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
@@ -1,118 +1,1777 @@
 use std::cmp;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fmt::Display;
 use std::io::{stdout, Stdout, Write};
+use std::mem::MaybeUninit;
+use std::ops::Range;
+use std::panic;
+use std::process::{Command, Output};
+use std::rc::Rc;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+use termion::cursor::DetectCursorPos;
 use termion::event::Key;
-use termion::input::TermRead;
 use termion::raw::{IntoRawMode, RawTerminal};
+use unicode_width::UnicodeWidthStr;
+
+use crate::aggregate::{Budget, FieldValues};
+use crate::ansi;
+use crate::bidi;
+use crate::cache::{Preview, PreviewCache};
+use crate::command::{describe_status, kill_group, Running, Template};
+use crate::diff::Side;
+use crate::events::{self, Event, EventSender, Recorder, Recording};
+use crate::groups::Groups;
+use crate::items::{Appender, Entries, OutputFn};
+use crate::keymap::{Action, Keymap};
+use crate::lscolors::LsColors;
+use crate::matcher::{build_regex, CaseMode, MatchMode, Matcher, Tiebreak};
+use crate::menu::{MenuItem, Operation};
+use crate::paths::PathInfo;
+use crate::prompt::{Prompt, PromptEvent};
+#[cfg(feature = "lua")]
+use crate::script::Script;
+use crate::sort::SelectionOrder;
+use crate::theme::{
+    Background, ColorDepth, Colors, CursorShape, Theme, BACKGROUND_QUERY, CURSOR_SHAPE_QUERY,
+};
+use crate::tree::Tree;
+use crate::walk::Listing;
+
+/// Height of the window the selector is drawn in, below the cursor.
+#[derive(Clone, Copy)]
+pub enum Height {
+    Rows(u16),
+    Percent(u16),
+}
+
+impl Height {
+    /// Returns the amount of rows for the provided terminal height, at least 2 (the header
+    /// and prompt lines).
+    fn rows(self, term_height: u16) -> u16 {
+        let rows = match self {
+            Height::Rows(rows) => rows,
+            Height::Percent(percent) => (term_height as u32 * percent as u32 / 100) as u16,
+        };
+        rows.clamp(2, term_height.max(2))
+    }
+}
+
+/// Columns drawn before each entry: the pointer at the entry under the cursor, the marker of
+/// the selected entries (if any), their distance to the cursor and their checkboxes, followed
+/// by a space.
+pub struct Gutter {
+    pub pointer: String,
+    pub marker: Option<String>,
+    /// Width of the distances to the cursor, shown if set.
+    pub relative_numbers: Option<usize>,
+    pub checkboxes: bool,
+}
+
+impl Default for Gutter {
+    fn default() -> Gutter {
+        Gutter {
+            pointer: String::from(">"),
+            marker: None,
+            relative_numbers: None,
+            checkboxes: false,
+        }
+    }
+}
+
+impl Gutter {
+    /// Returns the amount of columns taken before the entries.
+    pub fn width(&self) -> usize {
+        let marker = self.marker.as_ref().map_or(0, |m| m.width());
+        let checkbox = if self.checkboxes { CHECKBOX_WIDTH } else { 0 };
+        let number = self.relative_numbers.map_or(0, |width| width + 1);
+        self.pointer.width() + marker + 1 + number + checkbox
+    }
+
+    /// Returns the gutter of an entry, pointed at if it is under the cursor, which is the
+    /// provided amount of rows away.
+    fn render(&self, current: bool, selected: bool, distance: usize) -> String {
+        let mut gutter = if current {
+            self.pointer.clone()
+        } else {
+            " ".repeat(self.pointer.width())
+        };
+        if let Some(marker) = &self.marker {
+            if selected {
+                gutter.push_str(marker);
+            } else {
+                gutter.push_str(&" ".repeat(marker.width()));
+            }
+        }
+        gutter.push(' ');
+        if let Some(width) = self.relative_numbers {
+            gutter.push_str(&format!("{distance:>width$} "));
+        }
+        if self.checkboxes {
+            gutter.push_str(if selected { "[x] " } else { "[ ] " });
+        }
+        gutter
+    }
+}
+
+/// Glyphs of the scrollbar, for the rows of its thumb (the part of the list on screen) and
+/// the others.
+const SCROLLBAR_THUMB: char = '┃';
+const SCROLLBAR_TRACK: char = '│';
+
+/// Width of the "[x] " checkboxes.
+const CHECKBOX_WIDTH: usize = 4;
+
+/// Time a redraw may take before drawing is considered slow (e.g., a terminal over a
+/// high-latency connection, or a slow preview command), spacing the following redraws.
+const SLOW_REDRAW: Duration = Duration::from_millis(25);
+
+/// Spaces the redraws when drawing is slow, leaving as much time between them as the last
+/// one took, so that the keys received meanwhile are handled right away and only the latest
+/// state is drawn.
+#[derive(Default)]
+struct RedrawPacer {
+    /// Time taken by the last redraw.
+    duration: Duration,
+    /// When the last redraw ended.
+    end: Option<Instant>,
+    /// Whether a redraw was deferred.
+    pending: bool,
+}
+
+impl RedrawPacer {
+    /// Returns when the next redraw can happen, `None` if right away.
+    fn next_draw(&self) -> Option<Instant> {
+        let end = self.end?;
+        (self.duration > SLOW_REDRAW).then(|| end + self.duration)
+    }
+
+    /// Returns the time left before the deferred redraw, `None` if there is none.
+    fn wait(&self) -> Option<Duration> {
+        if !self.pending {
+            return None;
+        }
+        let next = self.next_draw().unwrap_or_else(Instant::now);
+        Some(next.saturating_duration_since(Instant::now()))
+    }
+
+    /// Redraws the selector, or defers it if drawing is slow and the last redraw too recent.
+    fn redraw(&mut self, selector: &mut SelectorTUI) -> Result<(), Box<dyn Error>> {
+        if self.next_draw().is_some_and(|next| Instant::now() < next) {
+            self.pending = true;
+            return Ok(());
+        }
+        self.draw(selector)
+    }
+
+    /// Redraws the selector right away, measuring how long it takes.
+    fn draw(&mut self, selector: &mut SelectorTUI) -> Result<(), Box<dyn Error>> {
+        let start = Instant::now();
+        selector.refresh_content()?;
+        let end = Instant::now();
+        self.duration = end - start;
+        self.end = Some(end);
+        self.pending = false;
+        Ok(())
+    }
+}
+
+/// Amount of entries whose preview command output is kept, so that moving back to them
+/// does not run the command again.
+const PREVIEW_CACHE_SIZE: usize = 64;
+
+/// Terminal attributes from before entering raw mode, restored by the panic hook.
+static ORIGINAL_TERMIOS: OnceLock<libc::termios> = OnceLock::new();
+
+/// Installs a panic hook restoring the terminal (attributes, colors and cursor) before the
+/// panic message is printed, so the message is readable and the shell left usable. Must be
+/// called before entering raw mode.
+fn install_panic_hook() {
+    let mut termios = MaybeUninit::<libc::termios>::uninit();
+    // SAFETY: tcgetattr initializes the struct when it succeeds
+    if unsafe { libc::tcgetattr(libc::STDOUT_FILENO, termios.as_mut_ptr()) } != 0 {
+        return;
+    }
+    // SAFETY: checked above that tcgetattr succeeded
+    if ORIGINAL_TERMIOS
+        .set(unsafe { termios.assume_init() })
+        .is_err()
+    {
+        return;
+    }
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if let Some(termios) = ORIGINAL_TERMIOS.get() {
+            // SAFETY: the attributes were read from the same file descriptor
+            unsafe { libc::tcsetattr(libc::STDOUT_FILENO, libc::TCSANOW, termios) };
+        }
+        let mut stdout = stdout();
+        let _ = write!(
+            stdout,
+            "{}{}\r\n",
+            termion::style::Reset,
+            termion::cursor::Show
+        );
+        let _ = stdout.flush();
+        default_hook(info);
+    }));
+}
+
+/// Scroll position of a screen region showing part of a longer content, scrolled
+/// independently of the other regions.
+#[derive(Default)]
+struct Scroll {
+    /// First content line shown.
+    offset: usize,
+    /// Amount of lines shown, as of the last draw.
+    rows: usize,
+}
+
+impl Scroll {
+    /// Scrolls the least needed for the provided line to be shown with `margin` lines around
+    /// it (as many as fit), within the content of the provided length.
+    fn follow(&mut self, line: usize, len: usize, margin: usize) {
+        let margin = cmp::min(margin, self.rows.saturating_sub(1) / 2);
+        if line < self.offset + margin {
+            self.offset = line.saturating_sub(margin);
+        } else if line + margin >= self.offset + self.rows {
+            self.offset = line + margin + 1 - self.rows;
+        }
+        self.clamp(len);
+    }
+
+    /// Scrolls half of the rows shown down or up.
+    fn scroll_half(&mut self, down: bool) {
+        let step = cmp::max(self.rows / 2, 1);
+        self.offset = if down {
+            self.offset + step
+        } else {
+            self.offset.saturating_sub(step)
+        };
+    }
+
+    /// Scrolls the least needed for the provided line to be shown whole with `margin` lines
+    /// around it (as many as fit), where each of the `len` lines takes the amount of rows
+    /// returned by `height`.
+    fn follow_heights(
+        &mut self,
+        line: usize,
+        len: usize,
+        margin: usize,
+        height: impl Fn(usize) -> usize,
+    ) {
+        let margin = cmp::min(margin, self.rows.saturating_sub(1) / 2);
+        if line < self.offset + margin {
+            self.offset = line.saturating_sub(margin);
+            return;
+        }
+        let last = cmp::min(line + margin, len.saturating_sub(1)).max(line);
+        let mut used: usize = (self.offset..=last).map(&height).sum();
+        while used > self.rows && self.offset < line {
+            used -= height(self.offset);
+            self.offset += 1;
+        }
+    }
+
+    /// Keeps the offset within the content of the provided length, without leaving rows
+    /// empty past its end.
+    fn clamp(&mut self, len: usize) {
+        self.offset = cmp::min(self.offset, len.saturating_sub(self.rows));
+    }
+}
+
+/// Optional settings for the selector beyond its entries.
+#[derive(Default)]
+pub struct SelectorOptions {
+    /// Command template run for the entry under the cursor, its output is shown below the list.
+    pub preview: Option<Template>,
+    /// Indices of the entries in the order they are listed.
+    pub order: Vec<usize>,
+    /// List the entries in a random order.
+    pub shuffle: bool,
+    /// List only a random subset of this many entries.
+    pub sample: Option<usize>,
+    /// List the entries upwards from the bottom of the screen, the header being right above
+    /// the prompt line.
+    pub reverse: bool,
+    /// File the received events are recorded to.
+    pub record: Option<String>,
+    /// Recorded events to replay instead of reading the terminal input.
+    pub replay: Option<Recording>,
+    /// Show an A–Z index in the last column, jumping to sections with the jump key.
+    pub index: bool,
+    /// Show a scrollbar in the last column, for long lists.
+    pub scrollbar: bool,
+    /// Amount of entries kept visible above and below the cursor when scrolling.
+    pub scroll_off: usize,
+    /// Keep the cursor at the first and last entries instead of wrapping around.
+    pub no_cycle: bool,
+    /// Only view the entries, without selecting them.
+    pub browse: bool,
+    /// Wrap long entries over several rows instead of cutting them.
+    pub wrap: bool,
+    /// Hierarchy of the entries, whose nodes can be collapsed and expanded.
+    pub tree: Option<Rc<Tree>>,
+    /// Select and deselect the descendants of a tree node along with it.
+    pub select_descendants: bool,
+    /// Entries shown dimmed, which cannot be selected.
+    pub disabled: HashSet<usize>,
+    /// Move the cursor over disabled entries without stopping on them.
+    pub skip_disabled: bool,
+    /// Lines of text shown below the header line, above the entries.
+    pub header: Vec<String>,
+    /// Show the header line with the counts and keybindings hint, otherwise it only appears
+    /// while a warning is shown.
+    pub header_line: bool,
+    /// Show the keybindings hint in the header line.
+    pub hint: bool,
+    /// Section headers shown above the first entry of each group.
+    pub groups: Option<Groups>,
+    /// Show the whole input line (or record) of the entry under the cursor in the preview
+    /// area, for multi-line records.
+    pub detail: bool,
+    /// Show only a query prompt line with the best match, cycled through with tab.
+    pub inline: bool,
+    /// Draw the selector in a window below the cursor instead of the whole screen.
+    pub height: Option<Height>,
+    /// Indices of the entries selected at startup.
+    pub selected: Vec<usize>,
+    /// Maximum amount of entries that can be selected at once.
+    pub max_select: Option<usize>,
+    /// Minimum amount of entries that must be selected to run the selection.
+    pub min_select: Option<usize>,
+    /// Keybindings used in the selector.
+    pub keymap: Keymap,
+    /// Pointer, marker and checkboxes drawn before the entries.
+    pub gutter: Gutter,
+    /// Colors set by the user, replacing those of the theme.
+    pub colors: Colors,
+    /// Amount of colors the terminal can display.
+    pub color_depth: ColorDepth,
+    /// Colors of the entries that are file paths, by file type and extension.
+    pub ls_colors: Option<LsColors>,
+    /// Whether the entries are file paths whose symlinks targets and broken links are shown.
+    pub paths: bool,
+    /// Files found with `--glob`, telling which ones are ignored or hidden.
+    pub listing: Option<Listing>,
+    /// List each entry is found in when comparing two lists.
+    pub diff: Option<Vec<Side>>,
+    /// Entry the cursor starts on, the first one if `None`.
+    pub cursor: Option<usize>,
+    /// Warning shown on the first draw.
+    pub warning: Option<String>,
+    /// Values of a field whose sum, average and count over the selected entries are shown
+    /// in a footer below the list.
+    pub sum: Option<FieldValues>,
+    /// Limit on the total of a field over the selected entries, shown in the header.
+    pub budget: Option<Budget>,
+    /// Ask for confirmation before quitting when entries are selected.
+    pub confirm_abort: bool,
+    /// Case sensitivity of the search, filter and select-by-pattern prompts.
+    pub case_mode: CaseMode,
+    /// Algorithm matching the search and filter queries.
+    pub match_mode: MatchMode,
+    /// Criteria ordering the filtered entries, ties keep the listed order.
+    pub tiebreak: Vec<Tiebreak>,
+    /// Script whose hooks are called and custom actions bound.
+    #[cfg(feature = "lua")]
+    pub script: Option<Rc<Script>>,
+    /// Command filtering the entries instead of the built-in matcher, receiving them on stdin
+    /// and printing the matching ones.
+    pub filter_cmd: Option<Template>,
+    /// Time after which the preview and filter commands are killed.
+    pub command_timeout: Duration,
+    /// Named pipe whose lines are appended to the list while the selector is running.
+    pub append_fifo: Option<String>,
+    /// Appends the lines read from `append_fifo` to the entries.
+    pub appender: Option<Appender>,
+    /// Order of the entries in the selection that is run.
+    pub selection_order: SelectionOrder,
+    /// Keys running the selection besides the bound one, whatever they are bound to.
+    pub expect: Vec<Key>,
+    /// Command run for each selected entry, confirmed before running the selection.
+    pub exec: Option<String>,
+    /// Operations offered for the entry under the cursor by the action menu.
+    pub menu: Vec<MenuItem>,
+    /// Formats what running the selection prints, shown on demand before running it, `None`
+    /// if the selection is not printed.
+    pub output: Option<OutputFn>,
+}
+
+/// Result of running the selector.
+pub struct Outcome {
+    /// Indices of the selected entries, `None` if the selector was quit or nothing was selected.
+    pub selection: Option<Vec<usize>>,
+    /// Amount of entries matching the filter or search when closing the selector (all without
+    /// either).
+    pub matched: usize,
+    /// Whether the selector was quit (or interrupted) instead of accepting a selection.
+    pub aborted: bool,
+    /// Indices of the entries selected when closing the selector, also if it was quit.
+    pub selected: Vec<usize>,
+    /// Filter query typed when closing the selector, or search query without a filter.
+    pub query: Option<String>,
+    /// Index of the entry under the cursor when closing the selector.
+    pub cursor: Option<usize>,
+    /// Key that closed the selector, `None` if its input ended first.
+    pub key: Option<Key>,
+}
+
+/// Operation run with the text submitted in the prompt.
+enum PromptKind {
+    /// Toggle the entries matching a regular expression.
+    SelectPattern,
+    /// Search entries incrementally, starting from the provided entry line.
+    Search(usize),
+    /// Filter entries incrementally, restoring the provided previous filter if cancelled.
+    Filter(Option<String>),
+}
+
+/// Operation run with the character typed as the next key.
+#[derive(Clone, Copy)]
+enum PendingKey {
+    /// Move to the next entry starting with the character.
+    Jump,
+    /// Mark the current entry with the character.
+    SetMark,
+    /// Move to the entry marked with the character.
+    GoToMark,
+    /// Move to the entry on screen labeled with the characters, the first one of two-letter
+    /// labels being kept until the second one is typed.
+    Hint(Option<char>),
+}
+
+/// Letters of the hint labels, in the order they are given to the rows on screen, the
+/// easiest to type first.
+const HINT_LETTERS: [char; 26] = [
+    'a', 's', 'd', 'f', 'g', 'h', 'j', 'k', 'l', 'q', 'w', 'e', 'r', 't', 'y', 'u', 'i', 'o', 'p',
+    'z', 'x', 'c', 'v', 'b', 'n', 'm',
+];
 
 /// UI and control methods for a text based list item selector.
 struct SelectorTUI {
-    entry_list: Vec<String>,
+    entries: Entries,
     stdout: RawTerminal<Stdout>,
     line_idx: usize,
     sel_tracker: Vec<usize>,
-    scroll_top: usize,
+    list_scroll: Scroll,
+    /// Columns of the entries hidden on their left.
+    h_offset: usize,
+    preview_scroll: Scroll,
+    /// Entry whose preview is scrolled by `preview_scroll`.
+    preview_entry: Option<usize>,
+    preview_cache: PreviewCache,
+    /// Preview command running in the background, by its process group id, with the entry
+    /// it is run for.
+    preview_job: Option<(u32, usize)>,
+    /// Commands of the action menu running in the background, by process group id, with
+    /// the menu entry they were picked from.
+    menu_jobs: HashMap<u32, usize>,
+    /// Filter command running in the background, by its process group id, with the entries
+    /// written to its stdin.
+    filter_job: Option<(u32, Vec<usize>)>,
+    /// Channel receiving the results of the commands run in the background, once the event
+    /// loop is started.
+    events_tx: Option<EventSender>,
+    /// Whether the events are replayed from a recording, nothing reading the terminal
+    /// replies to queries then.
+    replaying: bool,
+    path_infos: HashMap<usize, PathInfo>,
+    options: SelectorOptions,
+    theme: Theme,
+    warning: Option<String>,
+    prompt: Option<(PromptKind, Prompt)>,
+    search: Option<(String, Box<dyn Matcher>)>,
+    confirming_quit: bool,
+    /// Key running the selection whose confirmation is asked, answered with the next key.
+    confirming_run: Option<Key>,
+    /// Operation waiting for the next key, read as a character.
+    pending: Option<PendingKey>,
+    /// Text shown in a box over the list until the next key, with its title: what running
+    /// the selection would print, or the output of a command of the action menu.
+    output_preview: Option<(String, String)>,
+    /// Entry of the action menu under its cursor while it is open.
+    menu_cursor: Option<usize>,
+    /// Count typed before a movement, repeating it (or giving the entry number to go to).
+    count: Option<usize>,
+    /// Entries marked during the session, by the letter they were marked with.
+    marks: HashMap<char, usize>,
+    /// Entries laid out on screen by the last draw, in the listed order, labeled by hints.
+    on_screen: Vec<usize>,
+    /// Tree nodes whose descendants are hidden.
+    collapsed: HashSet<usize>,
+    visible: Vec<usize>,
+    /// Entries that can be listed, in their order before shuffling or sampling.
+    population: Vec<usize>,
+    filter: Option<String>,
+    filter_matcher: Option<Box<dyn Matcher>>,
+    match_mode: MatchMode,
+    /// Screen row where the selector window starts.
+    origin: u16,
+    /// Cursor shape reported by the terminal, restored when quitting.
+    cursor_shape: Option<CursorShape>,
 }
 
 impl SelectorTUI {
-    /// Create new instance of `SelectorTUI` with provided entry list as content.
-    pub fn new(entry_list: Vec<String>) -> Result<SelectorTUI, Box<dyn Error>> {
-        let selector = SelectorTUI {
-            entry_list,
+    /// Create new instance of `SelectorTUI` with provided entries as content.
+    pub fn new(entries: Entries, options: SelectorOptions) -> Result<SelectorTUI, Box<dyn Error>> {
+        let mut sel_tracker: Vec<usize> = options
+            .selected
+            .iter()
+            .filter(|i| !options.disabled.contains(i))
+            .map(|i| i + 2)
+            .collect();
+        sel_tracker.truncate(options.max_select.unwrap_or(usize::MAX));
+        let mut visible = options.order.clone();
+        if let Some(listing) = &options.listing {
+            visible.retain(|&idx| listing.is_shown(idx));
+        }
+        let population = options.order.clone();
+        let match_mode = options.match_mode;
+        let color_depth = options.color_depth;
+        install_panic_hook();
+        let mut selector = SelectorTUI {
+            entries,
             stdout: stdout().into_raw_mode()?,
             line_idx: 1,
-            sel_tracker: Vec::new(),
-            scroll_top: 0,
+            sel_tracker,
+            list_scroll: Scroll::default(),
+            h_offset: 0,
+            preview_scroll: Scroll::default(),
+            preview_entry: None,
+            preview_cache: PreviewCache::new(PREVIEW_CACHE_SIZE),
+            preview_job: None,
+            menu_jobs: HashMap::new(),
+            filter_job: None,
+            events_tx: None,
+            replaying: false,
+            path_infos: HashMap::new(),
+            options,
+            theme: Theme::for_background(Background::Dark, color_depth),
+            warning: None,
+            prompt: None,
+            search: None,
+            confirming_quit: false,
+            confirming_run: None,
+            pending: None,
+            output_preview: None,
+            menu_cursor: None,
+            count: None,
+            marks: HashMap::new(),
+            on_screen: Vec::new(),
+            collapsed: HashSet::new(),
+            visible,
+            population,
+            filter: None,
+            filter_matcher: None,
+            match_mode,
+            origin: 1,
+            cursor_shape: None,
         };
+        selector
+            .theme
+            .apply(&selector.options.colors, selector.options.color_depth);
+        selector.reshuffle();
+        selector.warning = selector.options.warning.take();
+        if let Some(entry) = selector.options.cursor {
+            if let Some(pos) = selector.visible.iter().position(|&idx| idx == entry) {
+                selector.line_idx = pos + 1;
+            }
+        }
         Ok(selector)
     }
 
-    /// Reloads the content to be displayed, clears the screen and draws the updated content.
-    pub fn refresh_content(&mut self) -> Result<(), Box<dyn Error>> {
-        let content = self.make_content();
-        let lines_to_draw = self.calculate_lines_to_draw(&content);
-        self.clear_scr()?;
-        self.draw_content(&lines_to_draw)?;
-        Ok(())
+    /// Reloads the content to be displayed, clears the screen and draws the updated content.
+    pub fn refresh_content(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.options.inline {
+            return self.draw_inline();
+        }
+        let lines_to_draw = self.calculate_lines_to_draw();
+        self.clear_scr()?;
+        if self.has_preview() {
+            self.draw_preview(lines_to_draw.len())?;
+        }
+        self.draw_content(&lines_to_draw)?;
+        if self.output_preview.is_some() {
+            self.draw_output_preview()?;
+        }
+        if self.menu_cursor.is_some() {
+            self.draw_menu()?;
+        }
+        if self.prompt.is_some() {
+            self.draw_prompt()?;
+        }
+        // warnings are shown once, on the next draw
+        self.warning = None;
+        Ok(())
+    }
+
+    /// Moves the cursor down one line. If the bottom is reached, moves cursor to the top
+    /// (or stays there with `no_cycle`).
+    /// With `skip_disabled`, keeps moving until an enabled entry is reached.
+    pub fn move_down(&mut self) {
+        let start = self.line_idx;
+        for _ in 0..self.visible.len().max(1) {
+            self.line_idx += 1;
+            if self.line_idx > self.visible.len() && self.options.no_cycle {
+                self.line_idx = start;
+                break;
+            }
+            if self.line_idx > self.visible.len() {
+                self.go_top();
+            }
+            if !self.on_skipped_entry() {
+                break;
+            }
+        }
+    }
+
+    /// Moves the cursor up one line. If the top is reached, moves cursor to the bottom (or
+    /// stays there with `no_cycle`).
+    /// With `skip_disabled`, keeps moving until an enabled entry is reached.
+    pub fn move_up(&mut self) {
+        let start = self.line_idx;
+        for _ in 0..self.visible.len().max(1) {
+            self.line_idx -= 1;
+            if self.line_idx < 1 && self.options.no_cycle {
+                self.line_idx = start;
+                break;
+            }
+            if self.line_idx < 1 {
+                self.go_bottom();
+            }
+            if !self.on_skipped_entry() {
+                break;
+            }
+        }
+    }
+
+    /// Returns true if the cursor is on a disabled entry that it should move over.
+    fn on_skipped_entry(&self) -> bool {
+        self.options.skip_disabled
+            && self
+                .current_entry()
+                .is_some_and(|e| self.options.disabled.contains(&e))
+    }
+
+    /// Moves the cursor the the last entry.
+    pub fn go_bottom(&mut self) {
+        self.line_idx = self.visible.len().max(1);
+    }
+
+    /// Moves the cursor to the first entry (below the header line).
+    pub fn go_top(&mut self) {
+        self.line_idx = 1;
+    }
+
+    /// Moves the cursor to the entry with the provided number (its input line number, as shown
+    /// with `--numbering`), showing a warning if it is not listed.
+    pub fn go_to_number(&mut self, number: usize) {
+        match self.visible.iter().position(|&idx| idx + 1 == number) {
+            Some(pos) => self.line_idx = pos + 1,
+            None => self.warning = Some(format!("entry {number} is not listed")),
+        }
+    }
+
+    /// Adds the digit to the count typed before a movement.
+    pub fn push_count_digit(&mut self, digit: usize) {
+        let count = self.count.unwrap_or(0);
+        self.count = Some(count.saturating_mul(10).saturating_add(digit));
+    }
+
+    /// Runs the movement `count` times, stopping at the ends of the list as in vim, or goes to
+    /// the entry numbered `count` for the first and last entry actions. Returns false if the
+    /// action does not take a count.
+    pub fn run_counted(&mut self, action: Action, count: usize) -> bool {
+        let reverse = self.options.reverse;
+        match action {
+            Action::Up | Action::Down => {
+                // the list grows upwards in the reverse layout
+                let down = (action == Action::Down) != reverse;
+                for _ in 0..count {
+                    if down && self.line_idx < self.visible.len() {
+                        self.move_down();
+                    } else if !down && self.line_idx > 1 {
+                        self.move_up();
+                    } else {
+                        break;
+                    }
+                }
+            }
+            Action::Top | Action::Bottom => self.go_to_number(count),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Returns the index of the entry in the current line, `None` if no entry is visible.
+    fn current_entry(&self) -> Option<usize> {
+        self.visible.get(self.line_idx - 1).copied()
+    }
+
+    /// Toggle selected status of the entry in current line, by adding respective
+    /// line number (entry index in `entries`, plus 2) to `selection_tracker` vector.
+    /// If the selection limit is reached, a warning is shown instead, unless the
+    /// limit is 1, in which case the new entry replaces the selected one. With
+    /// `select_descendants`, the descendants of a tree node are set to its new status.
+    pub fn toggle_selection(&mut self) {
+        let Some(entry) = self.current_entry() else {
+            return;
+        };
+        if self.options.disabled.contains(&entry) {
+            self.warning = Some(String::from("entry is disabled"));
+            return;
+        }
+        let mut group = vec![entry];
+        if let (Some(tree), true) = (&self.options.tree, self.options.select_descendants) {
+            group.extend(
+                tree.descendants(entry)
+                    .filter(|i| !self.options.disabled.contains(i)),
+            );
+        }
+        if self.sel_tracker.contains(&(entry + 2)) {
+            self.sel_tracker.retain(|i| !group.contains(&(i - 2)));
+        } else {
+            let missing: Vec<usize> = group
+                .into_iter()
+                .filter(|i| !self.sel_tracker.contains(&(i + 2)))
+                .collect();
+            if let Some(budget) = &self.options.budget {
+                // a single selection is replaced, freeing its share of the budget
+                let kept = if self.options.max_select == Some(1) {
+                    &[][..]
+                } else {
+                    &self.sel_tracker[..]
+                };
+                let left = budget.remaining(kept.iter().map(|i| i - 2));
+                let cost: f64 = missing.iter().map(|&i| budget.cost(i)).sum();
+                if cost > left {
+                    self.warning = Some(format!(
+                        "over budget: needs {}, {} left",
+                        budget.format(cost),
+                        budget.format(left)
+                    ));
+                    return;
+                }
+            }
+            match self.options.max_select {
+                Some(1) if missing.len() == 1 => self.sel_tracker.clear(),
+                Some(max) if self.sel_tracker.len() + missing.len() > max => {
+                    self.warning = Some(format!("selection limit reached ({max} max)"));
+                    return;
+                }
+                _ => {}
+            }
+            self.sel_tracker.extend(missing.iter().map(|i| i + 2));
+        }
+        #[cfg(feature = "lua")]
+        if let Some(script) = &self.options.script {
+            let selected = self.sel_tracker.contains(&(entry + 2));
+            if let Err(e) = script.on_toggle(&self.entries.items.get(entry), selected) {
+                self.warning = Some(format!("script error: {e}"));
+            }
+        }
+        self.move_down();
+    }
+
+    /// Runs the custom action of the script for the entry under the cursor, showing the
+    /// message it returns.
+    #[cfg(feature = "lua")]
+    pub fn run_script_action(&mut self, action: usize) {
+        let Some(script) = &self.options.script else {
+            return;
+        };
+        let line = self
+            .current_entry()
+            .map(|e| self.entries.items.get(e).into_owned());
+        let selected = self
+            .sel_tracker
+            .iter()
+            .map(|i| self.entries.items.get(i - 2).into_owned())
+            .collect();
+        match script.run_action(action, line.as_deref().unwrap_or_default(), selected) {
+            Ok(message) => self.warning = message,
+            Err(e) => self.warning = Some(format!("script error: {e}")),
+        }
+    }
+
+    /// Select all visible entries (but disabled ones), unless that would exceed the selection
+    /// limit.
+    pub fn select_all(&mut self) {
+        let missing: Vec<usize> = self
+            .visible
+            .iter()
+            .filter(|idx| !self.options.disabled.contains(idx))
+            .map(|idx| idx + 2)
+            .filter(|i| !self.sel_tracker.contains(i))
+            .collect();
+        let total = self.sel_tracker.len() + missing.len();
+        if let Some(max) = self.options.max_select.filter(|&m| m < total) {
+            self.warning = Some(format!("cannot select all, selection limit is {max}"));
+            return;
+        }
+        if let Some(budget) = &self.options.budget {
+            let needed: f64 = missing.iter().map(|i| budget.cost(i - 2)).sum();
+            let left = self.budget_left().unwrap_or_default();
+            if needed > left {
+                self.warning = Some(format!(
+                    "cannot select all, needs {} with {} left",
+                    budget.format(needed),
+                    budget.format(left)
+                ));
+                return;
+            }
+        }
+        self.sel_tracker.extend(missing);
+    }
+
+    /// Returns the budget left with the current selection, `None` without a budget.
+    fn budget_left(&self) -> Option<f64> {
+        let budget = self.options.budget.as_ref()?;
+        Some(budget.remaining(self.sel_tracker.iter().map(|i| i - 2)))
+    }
+
+    /// Deselect all entries.
+    pub fn select_none(&mut self) {
+        self.sel_tracker.clear();
+    }
+
+    /// Toggle the selected status of every entry whose input line matches the provided
+    /// regular expression (but disabled ones), stopping with a warning if the selection limit
+    /// is reached.
+    pub fn toggle_matching(&mut self, pattern: &str) {
+        let regex = match build_regex(pattern, self.options.case_mode) {
+            Ok(regex) => regex,
+            Err(_) => {
+                self.warning = Some(format!("invalid regular expression: {pattern}"));
+                return;
+            }
+        };
+        for idx in 0..self.entries.len() {
+            if self.options.disabled.contains(&idx) || !regex.is_match(&self.entries.items.get(idx))
+            {
+                continue;
+            }
+            if let Some(pos) = self.sel_tracker.iter().position(|&x| x == idx + 2) {
+                self.sel_tracker.remove(pos);
+            } else if self.sel_tracker.len() >= self.options.max_select.unwrap_or(usize::MAX) {
+                self.warning = Some(String::from(
+                    "selection limit reached, not all matches toggled",
+                ));
+                return;
+            } else if let Some(budget) = self
+                .options
+                .budget
+                .as_ref()
+                .filter(|b| b.cost(idx) > self.budget_left().unwrap_or_default())
+            {
+                let left = budget.format(self.budget_left().unwrap_or_default());
+                self.warning = Some(format!(
+                    "over budget ({left} left), not all matches toggled"
+                ));
+                return;
+            } else {
+                self.sel_tracker.push(idx + 2);
+            }
+        }
+    }
+
+    /// Opens the select-by-pattern prompt.
+    pub fn open_select_pattern_prompt(&mut self) {
+        self.prompt = Some((PromptKind::SelectPattern, Prompt::new("toggle matching: ")));
+    }
+
+    /// Opens the search prompt, searching from the current line.
+    pub fn open_search_prompt(&mut self) {
+        self.prompt = Some((PromptKind::Search(self.line_idx), Prompt::new("/")));
+    }
+
+    /// Moves the cursor to the next entry (previous if `forward` is false) matching the
+    /// search, wrapping around the list. Shows a warning if no entry matches.
+    pub fn search_next(&mut self, forward: bool) {
+        let Some((query, search)) = &self.search else {
+            return;
+        };
+        let len = self.visible.len();
+        if len == 0 {
+            return;
+        }
+        let found = (1..=len)
+            .map(|step| {
+                if forward {
+                    (self.line_idx - 1 + step) % len
+                } else {
+                    (self.line_idx - 1 + len * 2 - step) % len
+                }
+            })
+            .find(|&pos| {
+                search
+                    .find(&self.entries.display(self.visible[pos]))
+                    .is_some()
+            });
+        match found {
+            Some(pos) => self.line_idx = pos + 1,
+            None => self.warning = Some(format!("pattern not found: {query}")),
+        }
+    }
+
+    /// Waits for the character to jump to, read as the next key.
+    pub fn start_jump(&mut self) {
+        self.pending = Some(PendingKey::Jump);
+        self.warning = Some(String::from("jump to entry starting with:"));
+    }
+
+    /// Waits for the letter to mark the current entry with, read as the next key.
+    pub fn start_set_mark(&mut self) {
+        self.pending = Some(PendingKey::SetMark);
+        self.warning = Some(String::from("mark:"));
+    }
+
+    /// Waits for the letter of the mark to go to, read as the next key.
+    pub fn start_go_to_mark(&mut self) {
+        self.pending = Some(PendingKey::GoToMark);
+        self.warning = Some(String::from("go to mark:"));
+    }
+
+    /// Labels the entries on screen, waiting for the label of the one to move to.
+    pub fn start_hint(&mut self) {
+        self.pending = Some(PendingKey::Hint(None));
+        self.warning = Some(String::from("go to label:"));
+    }
+
+    /// Returns the amount of entries labeled by hints: those laid out on screen.
+    fn hint_count(&self) -> usize {
+        self.on_screen
+            .len()
+            .min(HINT_LETTERS.len() * HINT_LETTERS.len())
+    }
+
+    /// Returns the hint label of the entry, `None` if it is not labeled: a letter if all
+    /// labels fit in one, two otherwise.
+    fn hint_label(&self, idx: usize) -> Option<String> {
+        let n = self.on_screen.iter().position(|&e| e == idx)?;
+        if n >= self.hint_count() {
+            return None;
+        }
+        let label = if self.hint_count() <= HINT_LETTERS.len() {
+            HINT_LETTERS[n].to_string()
+        } else {
+            format!(
+                "{}{}",
+                HINT_LETTERS[n / HINT_LETTERS.len()],
+                HINT_LETTERS[n % HINT_LETTERS.len()]
+            )
+        };
+        Some(label)
+    }
+
+    /// Moves to the entry labeled with the typed letters, waiting for the second one of
+    /// two-letter labels. Other characters cancel the hints.
+    fn go_to_hint(&mut self, first: Option<char>, c: char) {
+        let Some(letter) = HINT_LETTERS.iter().position(|&l| l == c) else {
+            return;
+        };
+        let n = match first.and_then(|f| HINT_LETTERS.iter().position(|&l| l == f)) {
+            Some(first) => first * HINT_LETTERS.len() + letter,
+            None if self.hint_count() > HINT_LETTERS.len() => {
+                self.pending = Some(PendingKey::Hint(Some(c)));
+                self.warning = Some(format!("go to label: {c}"));
+                return;
+            }
+            None => letter,
+        };
+        if n >= self.hint_count() {
+            return;
+        }
+        let entry = self.on_screen[n];
+        if let Some(pos) = self.visible.iter().position(|&e| e == entry) {
+            self.line_idx = pos + 1;
+        }
+    }
+
+    /// Runs the pending operation with the provided character.
+    fn run_pending(&mut self, pending: PendingKey, c: char) {
+        match pending {
+            PendingKey::Hint(first) => self.go_to_hint(first, c),
+            PendingKey::Jump => self.jump_to(c),
+            PendingKey::SetMark => match self.current_entry() {
+                Some(entry) if c.is_alphabetic() => {
+                    self.marks.insert(c, entry);
+                    self.warning = Some(format!("marked '{c}'"));
+                }
+                _ => {}
+            },
+            PendingKey::GoToMark => {
+                let entry = self.marks.get(&c);
+                match entry.and_then(|e| self.visible.iter().position(|v| v == e)) {
+                    Some(pos) => self.line_idx = pos + 1,
+                    None if entry.is_some() => {
+                        self.warning = Some(format!("mark '{c}' is filtered out"))
+                    }
+                    None => self.warning = Some(format!("mark '{c}' not set")),
+                }
+            }
+        }
+    }
+
+    /// Moves the cursor to the next entry starting with the provided character (ignoring
+    /// case and leading spaces), wrapping around the list. Shows a warning if there is none.
+    pub fn jump_to(&mut self, c: char) {
+        if self.options.index && c.is_ascii_alphabetic() {
+            return self.jump_to_section(c.to_ascii_uppercase());
+        }
+        let len = self.visible.len();
+        let starts_with = |entry: String| {
+            let first = entry.trim_start().chars().next();
+            first.is_some_and(|f| f.to_lowercase().eq(c.to_lowercase()))
+        };
+        let found = (1..=len)
+            .map(|step| (self.line_idx - 1 + step) % len)
+            .find(|&pos| starts_with(self.entries.display(self.visible[pos])));
+        match found {
+            Some(pos) => self.line_idx = pos + 1,
+            None => self.warning = Some(format!("no entry starting with '{c}'")),
+        }
+    }
+
+    /// Moves the cursor to the first entry of the index section of the provided letter, or of
+    /// the next section if it is empty, assuming the list is sorted.
+    fn jump_to_section(&mut self, letter: char) {
+        let found = self
+            .visible
+            .iter()
+            .position(|&idx| section(&self.entries.display(idx)).is_some_and(|s| s >= letter));
+        match found {
+            Some(pos) => self.line_idx = pos + 1,
+            None => self.warning = Some(format!("no entry from '{letter}'")),
+        }
+    }
+
+    /// Sets the search query, moving the cursor to the first match from the current line.
+    fn set_search(&mut self, query: &str) {
+        if query.is_empty() {
+            self.search = None;
+            return;
+        }
+        let matcher = self.match_mode.matcher(query, self.options.case_mode);
+        let on_match = self
+            .current_entry()
+            .is_some_and(|entry| matcher.find(&self.entries.display(entry)).is_some());
+        self.search = Some((query.to_string(), matcher));
+        if !on_match {
+            self.search_next(true);
+        }
+    }
+
+    /// Switches to the next match mode, matching the current search and filter again.
+    pub fn cycle_match_mode(&mut self) {
+        self.match_mode = self.match_mode.next();
+        if let Some((query, _)) = self.search.take() {
+            self.set_search(&query);
+        }
+        if let Some(filter) = self.filter.clone() {
+            self.apply_filter(&filter);
+        }
+        self.warning = Some(format!("match mode: {}", self.match_mode.name()));
+    }
+
+    /// Shows the hidden files if they are not listed (or the ignored ones, without `hidden`),
+    /// hides them otherwise.
+    pub fn toggle_listed(&mut self, hidden: bool) {
+        let Some(listing) = &mut self.options.listing else {
+            return;
+        };
+        let (shown, kind) = if hidden {
+            (&mut listing.show_hidden, "hidden")
+        } else {
+            (&mut listing.show_ignored, "ignored")
+        };
+        *shown = !*shown;
+        let verb = if *shown { "showing" } else { "hiding" };
+        self.warning = Some(format!("{verb} {kind} files"));
+        let filter = self.filter.clone().unwrap_or_default();
+        self.apply_filter(&filter);
+    }
+
+    /// Passes the provided key to the open prompt, running its operation if the text is
+    /// submitted (or, for the search, whenever it is edited).
+    pub fn handle_prompt_key(&mut self, key: Key) {
+        let Some((kind, prompt)) = &mut self.prompt else {
+            return;
+        };
+        match (prompt.handle_key(key), kind) {
+            (PromptEvent::Edited, PromptKind::Search(origin)) => {
+                let text = prompt.text().to_string();
+                self.line_idx = *origin;
+                self.set_search(&text);
+            }
+            (PromptEvent::Edited, PromptKind::Filter(_)) => {
+                let text = prompt.text().to_string();
+                self.apply_filter(&text);
+            }
+            (PromptEvent::Edited, _) => {}
+            (PromptEvent::Cancel, PromptKind::Search(origin)) => {
+                self.line_idx = *origin;
+                self.search = None;
+                self.prompt = None;
+            }
+            (PromptEvent::Cancel, PromptKind::Filter(previous)) => {
+                let previous = previous.take().unwrap_or_default();
+                self.prompt = None;
+                self.apply_filter(&previous);
+            }
+            (PromptEvent::Cancel, _) => self.prompt = None,
+            // a number goes to that entry, as in vim
+            (PromptEvent::Submit(text), PromptKind::SelectPattern)
+                if !text.is_empty() && text.bytes().all(|b| b.is_ascii_digit()) =>
+            {
+                self.prompt = None;
+                self.go_to_number(text.parse().unwrap_or(usize::MAX));
+            }
+            (PromptEvent::Submit(text), PromptKind::SelectPattern) => {
+                self.prompt = None;
+                self.toggle_matching(&text);
+            }
+            (PromptEvent::Submit(_), PromptKind::Search(_) | PromptKind::Filter(_)) => {
+                self.prompt = None
+            }
+        }
+    }
+
+    /// Opens the query prompt of the inline mode, which stays open until quitting.
+    pub fn open_inline_prompt(&mut self) {
+        self.prompt = Some((PromptKind::Filter(None), Prompt::new("> ")));
+    }
+
+    /// Passes the provided key to the inline mode prompt, going back to the best match when
+    /// the query changes.
+    pub fn edit_inline_query(&mut self, key: Key) {
+        let query = |tui: &SelectorTUI| tui.prompt.as_ref().map(|(_, p)| p.text().to_string());
+        let previous = query(self);
+        self.handle_prompt_key(key);
+        if query(self) != previous {
+            self.go_top();
+        }
+    }
+
+    /// Lists the entries in a new random order with `shuffle`, and a new random subset with
+    /// `sample` (kept in the listed order unless shuffled), applying the filter again.
+    pub fn reshuffle(&mut self) {
+        if !self.options.shuffle && self.options.sample.is_none() {
+            return;
+        }
+        let mut positions: Vec<usize> = (0..self.population.len()).collect();
+        fastrand::shuffle(&mut positions);
+        if let Some(size) = self.options.sample {
+            positions.truncate(size);
+            if !self.options.shuffle {
+                positions.sort_unstable();
+            }
+        }
+        self.options.order = positions.iter().map(|&p| self.population[p]).collect();
+        let filter = self.filter.clone().unwrap_or_default();
+        self.apply_filter(&filter);
+    }
+
+    /// Appends the line to the entries, unless an entry with the same ID is already listed,
+    /// listing it last (or among the matches when filtering).
+    pub fn append(&mut self, line: &str) {
+        let Some(appender) = &mut self.options.appender else {
+            return;
+        };
+        if !appender.push(line) {
+            return;
+        }
+        let idx = self.entries.len() - 1;
+        if appender.is_disabled(&self.entries.items.get(idx)) {
+            self.options.disabled.insert(idx);
+        }
+        self.population.push(idx);
+        // a sample keeps its size
+        if self.options.sample.is_some() {
+            return;
+        }
+        self.options.order.push(idx);
+        match self.filter.clone() {
+            Some(filter) => self.apply_filter(&filter),
+            None => self.visible.push(idx),
+        }
+    }
+
+    /// Swaps the entry under the cursor with the next (or previous) listed one, the cursor
+    /// following it. Filtered lists, trees and groups are kept in their order.
+    pub fn move_entry(&mut self, down: bool) {
+        let refusal = if self.filter.is_some() {
+            Some("cannot move entries while filtering")
+        } else if self.options.tree.is_some() || self.options.groups.is_some() {
+            Some("cannot move entries of trees or groups")
+        } else {
+            None
+        };
+        if let Some(refusal) = refusal {
+            self.warning = Some(String::from(refusal));
+            return;
+        }
+        let pos = self.line_idx - 1;
+        let other = if down { pos + 1 } else { pos.wrapping_sub(1) };
+        if other >= self.visible.len() {
+            return;
+        }
+        // hidden files listed between both entries keep their place
+        let order = &mut self.options.order;
+        let from = order.iter().position(|&idx| idx == self.visible[pos]);
+        let to = order.iter().position(|&idx| idx == self.visible[other]);
+        if let (Some(from), Some(to)) = (from, to) {
+            order.swap(from, to);
+        }
+        self.visible.swap(pos, other);
+        self.line_idx = other + 1;
+    }
+
+    /// Collapses the tree node under the cursor, or moves to its parent if it is collapsed
+    /// or has no children.
+    pub fn collapse(&mut self) {
+        let (Some(tree), Some(entry)) = (&self.options.tree, self.current_entry()) else {
+            return;
+        };
+        if tree.has_children(entry) && self.collapsed.insert(entry) {
+            let filter = self.filter.clone().unwrap_or_default();
+            self.apply_filter(&filter);
+        } else if let Some(parent) = tree.parent(entry) {
+            if let Some(position) = self.visible.iter().position(|&idx| idx == parent) {
+                self.line_idx = position + 1;
+            }
+        }
+    }
+
+    /// Expands the tree node under the cursor, showing its children.
+    pub fn expand(&mut self) {
+        let Some(entry) = self.current_entry() else {
+            return;
+        };
+        if self.collapsed.remove(&entry) {
+            let filter = self.filter.clone().unwrap_or_default();
+            self.apply_filter(&filter);
+        }
+    }
+
+    /// Returns the `$LS_COLORS` style of the entry's file, `None` without `ls_colors`.
+    fn ls_style(&self, idx: usize) -> Option<&str> {
+        self.options
+            .ls_colors
+            .as_ref()?
+            .style(&self.entries.items.get(idx))
+    }
+
+    /// Returns whether the entry's file is a broken symlink or missing, along with the
+    /// target of symlinks, looking the file up the first time. `(false, None)` without `paths`.
+    fn path_info(&mut self, idx: usize) -> (bool, Option<String>) {
+        if !self.options.paths {
+            return (false, None);
+        }
+        let info = self
+            .path_infos
+            .entry(idx)
+            .or_insert_with(|| PathInfo::inspect(&self.entries.items.get(idx)));
+        (info.is_dead(), info.target().map(str::to_string))
+    }
+
+    /// Returns the entry with the marker of its tree node inserted after its indentation:
+    /// "▸" if collapsed, "▾" if expanded, a space without children.
+    fn with_tree_marker(&self, idx: usize, entry: &str) -> String {
+        let Some(tree) = &self.options.tree else {
+            return entry.to_string();
+        };
+        let marker = match (tree.has_children(idx), self.collapsed.contains(&idx)) {
+            (true, true) => "▸ ",
+            (true, false) => "▾ ",
+            (false, _) => "  ",
+        };
+        let indent = entry.len() - entry.trim_start().len();
+        format!("{}{marker}{}", &entry[..indent], &entry[indent..])
+    }
+
+    /// Opens the filter prompt, with the current filter typed.
+    pub fn open_filter_prompt(&mut self) {
+        let current = self.filter.clone().unwrap_or_default();
+        self.prompt = Some((
+            PromptKind::Filter(self.filter.clone()),
+            Prompt::with_text("filter: ", &current),
+        ));
+    }
+
+    /// Shows only the entries matching the provided query (all if it is empty), keeping the
+    /// cursor on the current entry if it is still visible.
+    pub fn apply_filter(&mut self, query: &str) {
+        self.cancel_filter();
+        let current = self.current_entry();
+        self.filter_matcher = None;
+        if query.is_empty() {
+            self.filter = None;
+            self.visible = self.options.order.clone();
+            // filtering shows matches in collapsed nodes too
+            if let Some(tree) = &self.options.tree {
+                self.visible
+                    .retain(|&idx| !tree.is_hidden(idx, &self.collapsed));
+            }
+        } else {
+            self.filter = Some(query.to_string());
+            if let Some(template) = &self.options.filter_cmd {
+                // the entries shown stay until the command prints the matching ones
+                let cmd = template.query_command(query);
+                self.start_filter_cmd(cmd);
+                return;
+            }
+            self.visible = self.filter_entries(query);
+        }
+        self.show_filtered(current);
+    }
+
+    /// Keeps the entries shown by the listing among the visible ones, with the cursor on the
+    /// provided entry if it is still visible (on the first one otherwise).
+    fn show_filtered(&mut self, current: Option<usize>) {
+        if let Some(listing) = &self.options.listing {
+            self.visible.retain(|&idx| listing.is_shown(idx));
+        }
+        let position = current.and_then(|c| self.visible.iter().position(|&idx| idx == c));
+        self.line_idx = position.map_or(1, |p| p + 1);
+    }
+
+    /// Returns the indices of the entries matching the query with the current match mode,
+    /// ordered by the tiebreak criteria.
+    fn filter_entries(&mut self, query: &str) -> Vec<usize> {
+        let matcher = self.match_mode.matcher(query, self.options.case_mode);
+        let mut ranked: Vec<(usize, Vec<i64>)> = Vec::new();
+        for &idx in &self.options.order {
+            let entry = self.entries.display(idx);
+            if let Some(score) = matcher.score(&entry) {
+                let keys = self.options.tiebreak.iter();
+                ranked.push((
+                    idx,
+                    keys.map(|t| t.key(idx, &entry, score, matcher.as_ref()))
+                        .collect(),
+                ));
+            }
+        }
+        ranked.sort_by(|(_, a), (_, b)| a.cmp(b));
+        self.filter_matcher = Some(matcher);
+        ranked.into_iter().map(|(idx, _)| idx).collect()
+    }
+
+    /// Starts the filter command in the background after writing all entries to its stdin,
+    /// the entries it prints being shown once it exits.
+    fn start_filter_cmd(&mut self, cmd: Command) {
+        let order = self.options.order.clone();
+        let lines: Vec<String> = order.iter().map(|&idx| self.entries.display(idx)).collect();
+        match self.start_job(cmd, Some(lines.join("\n"))) {
+            Ok(pgid) => self.filter_job = Some((pgid, order)),
+            Err(e) => self.warning = Some(format!("filter command failed: {e}")),
+        }
+    }
+
+    /// Kills the filter command running in the background (if any) along with the processes
+    /// it started, its output being outdated.
+    fn cancel_filter(&mut self) {
+        if let Some((pgid, _)) = self.filter_job.take() {
+            kill_group(pgid);
+        }
+    }
+
+    /// Returns the indices of the entries printed by the filter command, in its output order,
+    /// out of the provided ones written to its stdin.
+    fn filter_cmd_entries(&self, order: &[usize], output: &[u8]) -> Vec<usize> {
+        let lines: Vec<String> = order.iter().map(|&idx| self.entries.display(idx)).collect();
+        let mut positions: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (pos, line) in lines.iter().enumerate().rev() {
+            positions.entry(line).or_default().push(order[pos]);
+        }
+        let stdout = String::from_utf8_lossy(output);
+        stdout
+            .lines()
+            .filter_map(|line| positions.get_mut(line).and_then(Vec::pop))
+            .collect()
+    }
+
+    /// Returns the amount of entries matching the filter or search, all entries if there is
+    /// neither.
+    pub fn count_matched(&self) -> usize {
+        if self.filter.is_some() {
+            return self.visible.len();
+        }
+        match &self.search {
+            Some((_, search)) => (0..self.entries.len())
+                .filter(|&idx| search.find(&self.entries.display(idx)).is_some())
+                .count(),
+            None => self.entries.len(),
+        }
+    }
+
+    /// Returns true if enough entries are selected to run the selection, otherwise shows
+    /// a warning with the required amount.
+    pub fn can_run_selection(&mut self) -> bool {
+        match self.options.min_select {
+            Some(min) if self.sel_tracker.len() < min => {
+                self.warning = Some(format!("select at least {min} entries"));
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Returns vector with indices of selected entries, in the selection order.
+    pub fn retrieve_selection(&mut self) -> Option<Vec<usize>> {
+        if self.sel_tracker.is_empty() {
+            return None;
+        }
+        let mut selection: Vec<usize> = self.sel_tracker.iter().map(|i| i - 2).collect();
+        self.options
+            .selection_order
+            .arrange(&mut selection, &self.options.order);
+        Some(selection)
+    }
+
+    /// Shows over the list what running the selection would print, as formatted by the
+    /// output options.
+    pub fn show_output(&mut self) {
+        let selection = self.retrieve_selection();
+        let Some(output) = &self.options.output else {
+            return;
+        };
+        self.output_preview = Some(match selection.as_deref() {
+            Some(selection) => (
+                format!(" output of {} entries ", selection.len()),
+                output(selection),
+            ),
+            None => (
+                String::from(" output "),
+                String::from("nothing, no entry is selected"),
+            ),
+        });
+    }
+
+    /// Opens the action menu on the entry under the cursor, if there is one.
+    pub fn open_menu(&mut self) {
+        if self.current_entry().is_some() && !self.options.menu.is_empty() {
+            self.menu_cursor = Some(0);
+        }
+    }
+
+    /// Handles the key pressed while the action menu is open, with its cursor on the
+    /// provided entry: picking an entry (with its key or enter), moving the cursor or closing
+    /// the menu (with any other key). Returns the action picked, run by the caller.
+    pub fn menu_key(&mut self, cursor: usize, key: Key) -> Option<Action> {
+        let last = self.options.menu.len() - 1;
+        let with_key = self
+            .options
+            .menu
+            .iter()
+            .position(|item| matches!(key, Key::Char(c) if item.key == Some(c)));
+        let picked = match key {
+            _ if with_key.is_some() => with_key?,
+            Key::Up | Key::Ctrl('p') | Key::Char('k') => {
+                self.menu_cursor = Some(if cursor == 0 { last } else { cursor - 1 });
+                return None;
+            }
+            Key::Down | Key::Ctrl('n') | Key::Char('j') => {
+                self.menu_cursor = Some(if cursor == last { 0 } else { cursor + 1 });
+                return None;
+            }
+            Key::Char('\n') => cursor,
+            _ => return None,
+        };
+        let template = match &self.options.menu[picked].operation {
+            Operation::Action(action) => return Some(*action),
+            Operation::Command { template, .. } => template,
+        };
+        let entry = self.current_entry()?;
+        let cmd = template.item_command(&self.entries.items.get(entry));
+        // the result is shown once the command exits, the list staying usable meanwhile
+        self.warning = match self.start_job(cmd, None) {
+            Ok(pgid) => {
+                self.menu_jobs.insert(pgid, picked);
+                Some(format!("{}: running…", self.options.menu[picked].label))
+            }
+            Err(e) => Some(format!(
+                "{}: failed to run: {e}",
+                self.options.menu[picked].label
+            )),
+        };
+        None
+    }
+
+    /// Returns true if running the selection with the provided key can proceed, otherwise
+    /// (`exec` set) asks for confirmation, which is answered with the next key.
+    pub fn confirm_run(&mut self, key: Key) -> bool {
+        let Some(command) = &self.options.exec else {
+            return true;
+        };
+        if self.sel_tracker.is_empty() {
+            return true;
+        }
+        self.confirming_run = Some(key);
+        self.warning = Some(format!(
+            "Run '{command}' for {} entries? (y/n)",
+            self.sel_tracker.len()
+        ));
+        false
+    }
+
+    /// Returns true if quitting can proceed, otherwise (confirmation enabled and entries
+    /// selected) asks for confirmation, which is answered with the next key.
+    pub fn can_quit(&mut self) -> bool {
+        if !self.options.confirm_abort || self.sel_tracker.is_empty() {
+            return true;
+        }
+        self.confirming_quit = true;
+        self.warning = Some(format!(
+            "Discard {} selections? (y/n)",
+            self.sel_tracker.len()
+        ));
+        false
+    }
+
+    /// Clear screen, reset terminal format and set shell prompt position to the top.
+    pub fn quit(&mut self) -> Result<(), Box<dyn Error>> {
+        self.clear_scr()?;
+        self.reset_terminal(1)?;
+        write!(self.stdout, "{}", self.restore_cursor())?;
+        self.stdout.flush()?;
+        Ok(())
+    }
+
+    /// Query the terminal background color, the reply is received as an input event and
+    /// handled by `set_background`. Skipped when replaying, the recording holding the reply.
+    pub fn query_background(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.replaying {
+            return Ok(());
+        }
+        events::expect_reply();
+        write!(self.stdout, "{BACKGROUND_QUERY}")?;
+        self.stdout.flush()?;
+        Ok(())
+    }
+
+    /// Query the terminal cursor shape, the reply is received as an input event and restored
+    /// when quitting. Skipped when replaying.
+    fn query_cursor_shape(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.replaying {
+            return Ok(());
+        }
+        events::expect_reply();
+        write!(self.stdout, "{CURSOR_SHAPE_QUERY}")?;
+        self.stdout.flush()?;
+        Ok(())
+    }
+
+    /// Returns the escape sequences showing the cursor again, with the shape it had before
+    /// starting if the terminal reported it.
+    fn restore_cursor(&self) -> String {
+        let shape = self
+            .cursor_shape
+            .map(CursorShape::restore)
+            .unwrap_or_default();
+        format!("{}{shape}", termion::cursor::Show)
+    }
+
+    /// Switch to the default theme for the provided terminal background.
+    pub fn set_background(&mut self, background: Background) {
+        self.theme = Theme::for_background(background, self.options.color_depth);
+        self.theme
+            .apply(&self.options.colors, self.options.color_depth);
+    }
+
+    /// Scrolls the preview half of its height down or up.
+    pub fn scroll_preview(&mut self, down: bool) {
+        self.preview_scroll.scroll_half(down);
+    }
+
+    /// Scrolls the entries half of the screen width to the right or left, as far as the
+    /// longest entry on screen.
+    pub fn scroll_horizontal(&mut self, right: bool) {
+        if self.options.wrap {
+            return;
+        }
+        let width = (self.viewport().0 as usize).saturating_sub(self.options.gutter.width());
+        let step = (width / 2).max(1);
+        if !right {
+            self.h_offset = self.h_offset.saturating_sub(step);
+            return;
+        }
+        let first = self.list_scroll.offset;
+        let last = cmp::min(first + self.list_scroll.rows, self.visible.len());
+        let longest = self.visible[first.min(last)..last]
+            .iter()
+            .map(|&idx| self.entries.display(idx).width())
+            .max()
+            .unwrap_or(0);
+        self.h_offset = cmp::min(self.h_offset + step, longest.saturating_sub(width));
     }
 
-    /// Moves the cursor down one line. If the bottom is reached, moves cursor to the top.
-    pub fn move_down(&mut self) {
-        self.line_idx += 1;
-        if self.line_idx == self.entry_list.len() + 1 {
-            self.go_top();
+    /// Adapts the window to the new terminal size, keeping it within the screen.
+    pub fn resize(&mut self) {
+        let term_height = termion::terminal_size().unwrap_or((120, 40)).1;
+        if let Some(rows) = self.window_rows(term_height) {
+            self.origin = cmp::min(self.origin, (term_height + 1).saturating_sub(rows)).max(1);
         }
     }
 
-    /// Moves the cursor up one line. If the top is reached, moves cursor to the bottom.
-    pub fn move_up(&mut self) {
-        self.line_idx -= 1;
-        if self.line_idx < 1 {
-            self.go_bottom();
+    /// Runs the preview command again for the entry under the cursor, instead of showing its
+    /// cached output.
+    pub fn refresh_preview(&mut self) {
+        if let Some(entry) = self.current_entry() {
+            self.preview_cache.remove(entry);
         }
     }
 
-    /// Moves the cursor the the last entry.
-    pub fn go_bottom(&mut self) {
-        self.line_idx = self.entry_list.len();
+    /// Starts the command in the background, its result being received as `Event::JobDone`
+    /// with the returned process group id. It is killed with its group if it runs longer
+    /// than the command timeout.
+    fn start_job(&self, cmd: Command, input: Option<String>) -> Result<u32, String> {
+        let tx = self
+            .events_tx
+            .clone()
+            .ok_or("the event loop is not running")?;
+        let running = Running::spawn(cmd, input).map_err(|e| e.to_string())?;
+        let pgid = running.pgid();
+        let timeout = self.options.command_timeout;
+        thread::spawn(move || {
+            let output = running.wait(timeout).map_err(|e| e.to_string());
+            let _ = tx.send(Ok(Event::JobDone(pgid, output)));
+        });
+        Ok(pgid)
     }
 
-    /// Moves the cursor to the first entry (below the header line).
-    pub fn go_top(&mut self) {
-        self.line_idx = 1;
+    /// Kills the preview command running in the background (if any) along with the
+    /// processes it started.
+    fn cancel_preview(&mut self) {
+        if let Some((pgid, _)) = self.preview_job.take() {
+            kill_group(pgid);
+        }
     }
 
-    /// Toggle selected status of the entry in current line, by adding respective
-    /// line number (entry index in `entry_list`) to `selection_tracker` vector.
-    pub fn toggle_selection(&mut self) {
-        if self.sel_tracker.contains(&(self.line_idx + 1)) {
-            let idx_opt = self.sel_tracker.iter().position(|&x| x == self.line_idx + 1);
-            if let Some(index) = idx_opt {
-                self.sel_tracker.remove(index);
+    /// Handles the result of the command run in the background with the provided process
+    /// group id, ignored if it was cancelled.
+    pub fn finish_job(&mut self, pgid: u32, output: Result<Output, String>) {
+        if let Some((_, entry)) = self.preview_job.filter(|&(job, _)| job == pgid) {
+            self.preview_job = None;
+            // failures are kept until refreshed too
+            self.preview_cache.insert(entry, describe_preview(output));
+            return;
+        }
+        if let Some((_, order)) = self.filter_job.take_if(|(job, _)| *job == pgid) {
+            match output {
+                Ok(output) => {
+                    let current = self.current_entry();
+                    self.visible = self.filter_cmd_entries(&order, &output.stdout);
+                    self.show_filtered(current);
+                }
+                Err(e) => self.warning = Some(format!("filter command failed: {e}")),
             }
-        } else {
-            self.sel_tracker.push(self.line_idx + 1);
+            return;
         }
-        self.move_down();
+        let Some(item) = self.menu_jobs.remove(&pgid) else {
+            return;
+        };
+        let item = &self.options.menu[item];
+        if let Operation::Command { show: true, .. } = item.operation {
+            let preview = describe_preview(output);
+            self.output_preview = Some((format!(" {} ", item.label), preview.text));
+            return;
+        }
+        self.warning = Some(match output {
+            Ok(output) if output.status.success() => format!("{}: done", item.label),
+            Ok(output) => format!(
+                "{}: failed ({})",
+                item.label,
+                describe_status(output.status)
+            ),
+            Err(e) => format!("{}: failed: {e}", item.label),
+        });
     }
 
-    /// Select all entries.
-    pub fn select_all(&mut self) {
-        self.sel_tracker.clear();
-        for idx in 0..self.entry_list.len() {
-            self.sel_tracker.push(idx + 2);
+    /// Reset colors and attributes, drop the cached previews and file lookups and query the
+    /// terminal background again, so the display is adapted to the current terminal theme.
+    pub fn reload(&mut self) -> Result<(), Box<dyn Error>> {
+        write!(self.stdout, "{}", termion::style::Reset)?;
+        self.preview_cache.clear();
+        self.path_infos.clear();
+        self.query_background()
+    }
+
+    /// Returns the amount of rows of the window the selector is drawn in, `None` when it
+    /// takes the whole screen.
+    fn window_rows(&self, term_height: u16) -> Option<u16> {
+        if self.options.inline {
+            return Some(1);
         }
+        self.options.height.map(|height| height.rows(term_height))
     }
 
-    /// Deselect all entries.
-    pub fn select_none(&mut self) {
-        self.sel_tracker.clear();
+    /// Makes room for the selector window below the cursor when it does not take the whole
+    /// screen, scrolling the terminal if needed, and places the window origin at the cursor row.
+    pub fn reserve_window(&mut self) -> Result<(), Box<dyn Error>> {
+        let term_height = termion::terminal_size().unwrap_or((120, 40)).1;
+        let Some(rows) = self.window_rows(term_height) else {
+            return Ok(());
+        };
+        write!(self.stdout, "\r")?;
+        if rows > 1 {
+            write!(
+                self.stdout,
+                "{}{}",
+                "\n".repeat(rows as usize - 1),
+                termion::cursor::Up(rows - 1)
+            )?;
+        }
+        // without a reply, the cursor is assumed to be at the bottom after scrolling
+        self.origin = self
+            .stdout
+            .cursor_pos()
+            .map_or(term_height - rows + 1, |(_, row)| row);
+        Ok(())
     }
 
-    /// Returns vector with indices of selected entries.
-    pub fn retrieve_selection(&mut self) -> Option<Vec<usize>> {
-        if self.sel_tracker.is_empty() {
-            return None;
+    /// Returns the width and height of the area the selector is drawn in, the whole screen
+    /// unless it is drawn in a window.
+    fn viewport(&self) -> (u16, u16) {
+        let (width, term_height) = termion::terminal_size().unwrap_or((120, 40));
+        match self.window_rows(term_height) {
+            Some(rows) => (
+                width,
+                rows.min((term_height + 1).saturating_sub(self.origin)),
+            ),
+            None => (width, term_height),
         }
-        Some(self.sel_tracker.iter().map(|i| i - 2).collect())
     }
 
-    /// Clear screen, reset terminal format and set shell prompt position to the top.
-    pub fn quit(&mut self) -> Result<(), Box<dyn Error>> {
-        self.clear_scr()?;
-        self.reset_terminal(1)?;
-        write!(self.stdout, "{}", termion::cursor::Show)?;
+    /// Clears the area the selector is drawn in, the whole screen unless it is drawn in a
+    /// window.
+    fn clear_viewport(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.window_rows(self.viewport().1).is_none() {
+            write!(self.stdout, "{}", termion::clear::All)?;
+            return Ok(());
+        }
+        for row in 0..self.viewport().1 {
+            write!(
+                self.stdout,
+                "{}{}",
+                termion::cursor::Goto(1, self.origin + row),
+                termion::clear::CurrentLine
+            )?;
+        }
         Ok(())
     }
 
     /// Clear the screen, adjust cursor position to top-left, hide the cursor.
     fn clear_scr(&mut self) -> Result<(), Box<dyn Error>> {
+        self.clear_viewport()?;
         write!(
             self.stdout,
-            "{}{}{}",
-            termion::clear::All,
-            termion::cursor::Goto(1, 1),
+            "{}{}",
+            termion::cursor::Goto(1, self.origin),
             termion::cursor::Hide
         )?;
         Ok(())
@@ -122,131 +1781,1112 @@ impl SelectorTUI {
     /// Provide line number for the shell prompt to be positioned
     /// after printing output (if any) and closing.
     fn reset_terminal(&mut self, prompt_line: u16) -> Result<(), Box<dyn Error>> {
+        write!(self.stdout, "{}", self.theme.reset)?;
+        self.clear_viewport()?;
         write!(
             self.stdout,
-            "{}{}{}{}{}",
-            termion::color::Fg(termion::color::Reset),
-            termion::color::Bg(termion::color::Reset),
-            termion::clear::All,
-            termion::cursor::Goto(1, prompt_line),
+            "{}{}",
+            termion::cursor::Goto(1, self.origin + prompt_line - 1),
             termion::cursor::Show
         )?;
         Ok(())
     }
 
-    /// Iterate through content drawing each line on screen.
+    /// Iterate through content drawing each line on screen, cut to the screen width so that
+    /// long lines do not wrap.
     fn draw_content(&mut self, lines: &[String]) -> Result<(), Box<dyn Error>> {
+        let (width, term_height) = self.viewport();
         for (num, line) in lines.iter().enumerate() {
-            self.write_line_stdout(num + 1, line)?;
+            // the index sidebar or scrollbar takes the last column below the header
+            let width = if num >= self.pinned_rows() {
+                self.list_width()
+            } else {
+                width as usize
+            };
+            let line = ansi::truncate(line, width);
+            self.write_line_stdout(self.screen_row(num + 1, term_height), line)?;
+        }
+        if self.options.index {
+            self.draw_index(width, term_height)?;
+        }
+        if self.options.scrollbar {
+            self.draw_scrollbar(width, term_height)?;
+        }
+        self.stdout.flush()?;
+        Ok(())
+    }
+
+    /// Draws the A–Z index in the last column of the list rows, spreading the letters from A
+    /// to Z over them if there are fewer than 26, and highlighting the section of the current
+    /// entry (the closest shown letter before it).
+    fn draw_index(&mut self, width: u16, term_height: u16) -> Result<(), Box<dyn Error>> {
+        let rows = self.list_scroll.rows.min(26);
+        let letters: Vec<char> = (0..rows)
+            .map(|row| (b'A' + (row * 25 / rows.saturating_sub(1).max(1)) as u8) as char)
+            .collect();
+        let current = self
+            .current_entry()
+            .and_then(|e| section(&self.entries.display(e)));
+        let current_row = current.and_then(|c| letters.iter().rposition(|&l| l <= c));
+        for (row, letter) in letters.into_iter().enumerate() {
+            let style = if current_row == Some(row) {
+                &self.theme.selected
+            } else {
+                &self.theme.header
+            };
+            let screen_row = self.screen_row(row + 1 + self.pinned_rows(), term_height);
+            write!(
+                self.stdout,
+                "{}{style}{letter}{}",
+                termion::cursor::Goto(width, self.origin + screen_row as u16 - 1),
+                self.theme.reset
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Draws the scrollbar in the last column of the list rows, its thumb showing which part
+    /// of the list is on screen, relative to the whole list. Nothing is drawn when the list
+    /// fits in the screen.
+    fn draw_scrollbar(&mut self, width: u16, term_height: u16) -> Result<(), Box<dyn Error>> {
+        let rows = self.list_scroll.rows;
+        let total = self.visible.len();
+        if total <= rows || rows == 0 {
+            return Ok(());
+        }
+        let thumb_rows = (rows * rows / total).max(1);
+        let thumb_start = cmp::min(self.list_scroll.offset * rows / total, rows - thumb_rows);
+        // the last entry is only reached with the thumb at the bottom
+        let thumb_start = if self.list_scroll.offset + rows >= total {
+            rows - thumb_rows
+        } else {
+            thumb_start
+        };
+        for row in 0..rows {
+            let glyph = if (thumb_start..thumb_start + thumb_rows).contains(&row) {
+                SCROLLBAR_THUMB
+            } else {
+                SCROLLBAR_TRACK
+            };
+            let screen_row = self.screen_row(row + 1 + self.pinned_rows(), term_height);
+            write!(
+                self.stdout,
+                "{}{glyph}",
+                termion::cursor::Goto(width, self.origin + screen_row as u16 - 1)
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Runs the preview command for the entry under the cursor, unless its output is cached (or
+    /// takes its whole record with `detail`), and draws its output in the rows not taken by the
+    /// provided amount of content lines, separated from them by a line.
+    fn draw_preview(&mut self, content_lines: usize) -> Result<(), Box<dyn Error>> {
+        let term_size = self.viewport();
+        let width = term_size.0 as usize;
+        let last_line = term_size.1.saturating_sub(1) as usize;
+        let (separator_line, first_line, rows) = if self.options.reverse {
+            let separator_line = last_line.saturating_sub(content_lines);
+            (separator_line, 1, separator_line.saturating_sub(1))
+        } else {
+            let separator_line = content_lines + 1;
+            (
+                separator_line,
+                separator_line + 1,
+                last_line.saturating_sub(separator_line),
+            )
+        };
+
+        // the cursor moved away from the entry whose preview is running
+        if self
+            .preview_job
+            .is_some_and(|(_, entry)| Some(entry) != self.current_entry())
+        {
+            self.cancel_preview();
+        }
+        let preview = match (&self.options.preview, self.current_entry()) {
+            (Some(template), Some(entry)) => match self.preview_cache.get(entry) {
+                Some(preview) => preview.clone(),
+                // the preview is drawn once its command exits
+                None if self.preview_job.is_some() => loading_preview(),
+                None => {
+                    let cmd = template.command(&self.entries.items.get(entry));
+                    match self.start_job(cmd, None) {
+                        Ok(pgid) => {
+                            self.preview_job = Some((pgid, entry));
+                            loading_preview()
+                        }
+                        Err(e) => {
+                            let preview = describe_preview(Err(e));
+                            self.preview_cache.insert(entry, preview.clone());
+                            preview
+                        }
+                    }
+                }
+            },
+            (None, Some(entry)) if self.options.detail => Preview {
+                text: self.entries.items.get(entry).into_owned(),
+                failed: false,
+            },
+            _ => return Ok(()),
+        };
+        let lines: Vec<&str> = preview.text.lines().collect();
+        if self.preview_entry != self.current_entry() {
+            self.preview_entry = self.current_entry();
+            self.preview_scroll.offset = 0;
+        }
+        self.preview_scroll.rows = rows;
+        self.preview_scroll.clamp(lines.len());
+
+        let separator: String = (0..width).map(|_| '─').collect();
+        self.write_line_stdout(separator_line, separator)?;
+        for (num, line) in lines
+            .iter()
+            .skip(self.preview_scroll.offset)
+            .take(rows)
+            .enumerate()
+        {
+            let line = if self.entries.sanitize {
+                ansi::sanitize(line)
+            } else {
+                line.to_string()
+            };
+            let mut truncated = ansi::truncate(&line, width).into_owned();
+            if preview.failed {
+                truncated = format!("{}{truncated}{}", self.theme.warning, self.theme.reset);
+            }
+            self.write_line_stdout(first_line + num, truncated)?;
+        }
+        Ok(())
+    }
+
+    /// Draws the text running the selection would print in a box over the list, newlines
+    /// ending its rows and the other control characters (e.g., the NUL of `--read0`) being
+    /// shown in caret notation.
+    fn draw_output_preview(&mut self) -> Result<(), Box<dyn Error>> {
+        let Some((title, text)) = &self.output_preview else {
+            return Ok(());
+        };
+        let (width, term_height) = self.viewport();
+        let max_rows = (term_height as usize).saturating_sub(4);
+        let mut lines: Vec<String> = text
+            .strip_suffix('\n')
+            .unwrap_or(text)
+            .split('\n')
+            .map(ansi::visualize_controls)
+            .collect();
+        let title = title.clone();
+        let mut footer = String::from(" any key closes ");
+        if lines.len() > max_rows {
+            footer = format!(
+                " {} more lines, any key closes ",
+                lines.len() - max_rows + 1
+            );
+            lines.truncate(max_rows.saturating_sub(1));
+            lines.push(String::from("…"));
+        }
+        self.draw_box(
+            &title,
+            &lines,
+            &footer,
+            (width as usize).saturating_sub(6),
+            None,
+        )
+    }
+
+    /// Draws the action menu over the list, each entry preceded by the key picking it.
+    fn draw_menu(&mut self) -> Result<(), Box<dyn Error>> {
+        let Some(cursor) = self.menu_cursor else {
+            return Ok(());
+        };
+        let lines: Vec<String> = self
+            .options
+            .menu
+            .iter()
+            .enumerate()
+            .map(|(num, item)| {
+                let pointer = if num == cursor { '>' } else { ' ' };
+                format!("{pointer} {} {}", item.key.unwrap_or(' '), item.label)
+            })
+            .collect();
+        let footer = " enter picks, esc closes ";
+        let wanted = lines
+            .iter()
+            .map(|l| l.width())
+            .max()
+            .unwrap_or(0)
+            .max(footer.width() - 2);
+        let inner_width = wanted.min((self.viewport().0 as usize).saturating_sub(6));
+        self.draw_box(" actions ", &lines, footer, inner_width, Some(cursor))
+    }
+
+    /// Draws the lines in a box with the title and footer in its borders, centered in height
+    /// over the list, the highlighted line being drawn as the entry under the cursor.
+    fn draw_box(
+        &mut self,
+        title: &str,
+        lines: &[String],
+        footer: &str,
+        inner_width: usize,
+        highlighted: Option<usize>,
+    ) -> Result<(), Box<dyn Error>> {
+        let term_height = self.viewport().1;
+        let border = |text: &str, left: char, right: char| {
+            let text = ansi::truncate(text, inner_width + 2);
+            let fill: String = "─".repeat(inner_width + 2 - text.width());
+            format!("{left}{text}{fill}{right}")
+        };
+        let mut rows = vec![border(title, '┌', '┐')];
+        for line in lines {
+            let line = ansi::truncate(line, inner_width);
+            let padding = " ".repeat(inner_width - line.width());
+            rows.push(format!("│ {line}{padding} │"));
         }
+        rows.push(border(footer, '└', '┘'));
+        let top = (term_height as usize).saturating_sub(rows.len()) / 2;
+        for (num, row) in rows.iter().enumerate() {
+            // the first row is the top border
+            let style = if highlighted.is_some_and(|h| h + 1 == num) {
+                &self.theme.cursor
+            } else {
+                &self.theme.selected
+            };
+            write!(
+                self.stdout,
+                "{}{style}{row}{}",
+                termion::cursor::Goto(2, self.origin + (top + num) as u16),
+                self.theme.reset
+            )?;
+        }
+        self.stdout.flush()?;
+        Ok(())
+    }
+
+    /// Draws the line of the inline mode: the query prompt followed by the entry under the
+    /// cursor and its position among the matching entries.
+    fn draw_inline(&mut self) -> Result<(), Box<dyn Error>> {
+        let Some((_, prompt)) = &self.prompt else {
+            return Ok(());
+        };
+        let (query, column) = prompt.render();
+        let position = if self.visible.is_empty() {
+            0
+        } else {
+            self.line_idx
+        };
+        let counter = format!("[{position}/{}]", self.visible.len());
+        let available =
+            (self.viewport().0 as usize).saturating_sub(query.width() + counter.width() + 4);
+        let entry = self
+            .current_entry()
+            .map(|e| self.entries.display(e))
+            .unwrap_or_default();
+        let entry = ansi::truncate(&entry, available);
+        write!(
+            self.stdout,
+            "{}{}{query}  {}{entry}{}  {counter}{}{}",
+            termion::cursor::Goto(1, self.origin),
+            termion::clear::CurrentLine,
+            self.theme.selected,
+            self.theme.reset,
+            termion::cursor::Goto(column as u16 + 1, self.origin),
+            termion::cursor::Show
+        )?;
+        self.stdout.flush()?;
+        Ok(())
+    }
+
+    /// Draws the open prompt on the last line of the screen, showing the cursor in it.
+    fn draw_prompt(&mut self) -> Result<(), Box<dyn Error>> {
+        let Some((_, prompt)) = &self.prompt else {
+            return Ok(());
+        };
+        let (line, column) = prompt.render();
+        let row = self.origin + self.viewport().1 - 1;
+        write!(
+            self.stdout,
+            "{}{}{}{}{}",
+            termion::cursor::Goto(1, row),
+            termion::style::Reset,
+            line,
+            termion::cursor::Goto(column as u16 + 1, row),
+            termion::cursor::Show
+        )?;
         self.stdout.flush()?;
         Ok(())
     }
 
-    /// Returns vector with the content lines to draw, determined based on the scroll level
-    /// and the amount of lines that fit in the screen depending on terminal height.
-    fn calculate_lines_to_draw(&mut self, lines: &[String]) -> Vec<String> {
-        let term_size = termion::terminal_size().unwrap_or((120, 40));
-        let max_rows = (term_size.1 - 1) as usize;
+    /// Returns true if the preview area is shown, with the preview command output or the
+    /// detail of the entry.
+    fn has_preview(&self) -> bool {
+        self.options.preview.is_some() || self.options.detail
+    }
+
+    /// Returns the amount of rows available for the header and entry lines, which is half
+    /// of the screen when a preview is shown.
+    fn list_rows(&self, term_height: u16) -> usize {
+        let rows = term_height.saturating_sub(1) as usize;
+        if self.has_preview() {
+            rows / 2
+        } else {
+            rows
+        }
+    }
+
+    /// Returns vector with the content lines to draw, determined based on the list scroll
+    /// position and the amount of lines that fit in the screen depending on terminal height.
+    fn calculate_lines_to_draw(&mut self) -> Vec<String> {
+        let term_size = self.viewport();
+        // the header is pinned above the entries, and the footer below them
+        let footer_rows = usize::from(self.options.sum.is_some());
+        self.list_scroll.rows = self
+            .list_rows(term_size.1)
+            .saturating_sub(self.pinned_rows() + footer_rows);
+        if !self.options.wrap && self.options.groups.is_none() {
+            self.list_scroll.follow(
+                self.line_idx - 1,
+                self.visible.len(),
+                self.options.scroll_off,
+            );
+            let first = self.list_scroll.offset;
+            let last = cmp::min(first + self.list_scroll.rows, self.visible.len());
+            return self.make_content(first..last);
+        }
+
+        // entries take a varying amount of rows (wrapped, or below a group header), the scroll
+        // position still counts entries
+        let text_width = self
+            .list_width()
+            .saturating_sub(self.options.gutter.width())
+            .max(1);
+        let heights: Vec<usize> = (0..self.visible.len())
+            .map(|pos| {
+                let rows = if self.options.wrap {
+                    self.entries
+                        .display(self.visible[pos])
+                        .width()
+                        .div_ceil(text_width)
+                        .max(1)
+                } else {
+                    1
+                };
+                rows + usize::from(self.group_title(pos).is_some())
+            })
+            .collect();
+        self.list_scroll.follow_heights(
+            self.line_idx - 1,
+            self.visible.len(),
+            self.options.scroll_off,
+            |pos| heights.get(pos).copied().unwrap_or(1),
+        );
+        let first = self.list_scroll.offset.min(self.visible.len());
+        let mut last = first;
+        let mut used = 0;
+        // an entry whose group header takes the last row is left out
+        while last < self.visible.len()
+            && used + usize::from(self.group_title(last).is_some()) < self.list_scroll.rows
+        {
+            used += heights[last];
+            last += 1;
+        }
+        self.make_content(first..last)
+    }
+
+    /// Returns the amount of rows pinned above the entries: the header line (if shown) and the
+    /// lines of the custom header.
+    fn pinned_rows(&self) -> usize {
+        usize::from(self.shows_header_line()) + self.options.header.len()
+    }
+
+    /// Returns true if the header line is drawn, which without `header_line` only shows
+    /// warnings.
+    fn shows_header_line(&self) -> bool {
+        self.options.header_line || self.warning.is_some()
+    }
 
-        let cur_line = self.line_idx + 1;
-        let mut scroll_top = self.scroll_top;
-        if cur_line <= scroll_top {
-            scroll_top = 0;
-        } else if cur_line - scroll_top > max_rows {
-            scroll_top = cur_line - max_rows;
+    /// Returns the width available to the entries, which is the screen width without the
+    /// column of the index sidebar or scrollbar.
+    fn list_width(&self) -> usize {
+        let width = self.viewport().0 as usize;
+        if self.options.index || self.options.scrollbar {
+            width.saturating_sub(1)
+        } else {
+            width
         }
-        self.scroll_top = scroll_top;
+    }
 
-        let last_idx = cmp::min(max_rows, lines.len());
-        Vec::from(&lines[scroll_top..scroll_top + last_idx])
+    /// Returns the screen row of the provided content line (both starting at 1), counting
+    /// upwards from the row above the prompt line in the reverse layout.
+    fn screen_row(&self, line_num: usize, term_height: u16) -> usize {
+        if self.options.reverse {
+            (term_height as usize).saturating_sub(line_num)
+        } else {
+            line_num
+        }
     }
 
     // Writes the provided text in the specified line number.
-    fn write_line_stdout(&mut self, line_num: usize, display_text: impl Display) -> Result<(), Box<dyn Error>> {
+    fn write_line_stdout(
+        &mut self,
+        line_num: usize,
+        display_text: impl Display,
+    ) -> Result<(), Box<dyn Error>> {
         write!(
             self.stdout,
             "{}{}",
-            termion::cursor::Goto(1, line_num as u16),
+            termion::cursor::Goto(1, self.origin + line_num as u16 - 1),
             display_text
         )?;
         Ok(())
     }
 
-    /// Returns vector consolidating header line and entry lines.
-    fn make_content(&mut self) -> Vec<String> {
-        let mut lines = vec![self.make_header_line()];
-        lines.append(&mut self.make_entries_into_lines());
+    /// Returns vector consolidating the header line (if shown), the lines of the entries in
+    /// the provided range of positions and the footer line, if any.
+    fn make_content(&mut self, range: Range<usize>) -> Vec<String> {
+        let mut lines: Vec<String> = self
+            .shows_header_line()
+            .then(|| self.make_header_line())
+            .into_iter()
+            .collect();
+        // pinned lines are listed upwards too when reversed, so they read top to bottom
+        let mut header: Vec<String> = self
+            .options
+            .header
+            .iter()
+            .map(|l| ansi::sanitize(l))
+            .collect();
+        if self.options.reverse {
+            header.reverse();
+        }
+        lines.extend(header);
+        let first = range.start;
+        let entries = self.make_entries_into_lines(range);
+        let width = self.list_width();
+        let mut rows: Vec<String> = Vec::new();
+        for (pos, entry) in (first..).zip(entries) {
+            // headers come first in the listing order, so below their group when reversed
+            if let Some(title) = self.group_title(pos) {
+                let title = ansi::truncate(&ansi::sanitize(title), width).into_owned();
+                rows.push(format!(
+                    "{}{title}{}",
+                    termion::style::Bold,
+                    termion::style::Reset
+                ));
+            }
+            if self.options.wrap {
+                // continuation rows are indented past the gutter, and listed upwards when reversed
+                let mut entry_rows = ansi::wrap(&entry, width, self.options.gutter.width());
+                if self.options.reverse {
+                    entry_rows.reverse();
+                }
+                rows.extend(entry_rows);
+            } else {
+                rows.push(entry);
+            }
+        }
+        rows.truncate(self.list_scroll.rows);
+        lines.extend(rows);
+        lines.extend(self.make_footer_line());
         lines
     }
 
-    /// Returns String with header line showing 'tagged entry count / total entries' and keybindings.
-    fn make_header_line(&mut self) -> String {
-        format!(
-            "{}{} ({} selected / {} total)  [l/right:select  enter:run selection  q/h/left:quit  a:select all  n:deselect all] ",
-            termion::color::Fg(termion::color::Black),
-            termion::color::Bg(termion::color::White),
-            self.sel_tracker.len(),
-            self.entry_list.len()
-        )
+    /// Returns the title of the group of the entry at the provided position if it is the
+    /// first of its group to be listed, for showing its header above it.
+    fn group_title(&self, pos: usize) -> Option<&str> {
+        let groups = self.options.groups.as_ref()?;
+        let group = groups.group(self.visible[pos])?;
+        if pos > 0 && groups.group(self.visible[pos - 1]) == Some(group) {
+            return None;
+        }
+        Some(groups.title(group))
     }
 
-    /// Returns Vec<String> with each line to be displayed from the entry list,
+    /// Returns the footer line with the sum, average and count of the summed field over the
+    /// selected entries, `None` without `sum`.
+    fn make_footer_line(&self) -> Option<String> {
+        let values = self.options.sum.as_ref()?;
+        let (sum, count) = values.sum(self.sel_tracker.iter().map(|i| i - 2));
+        let average = if count == 0 { 0.0 } else { sum / count as f64 };
+        Some(format!(
+            "{} sum: {}  avg: {}  count: {} {}",
+            self.theme.header,
+            values.format(sum),
+            values.format(average),
+            count,
+            self.theme.reset
+        ))
+    }
+
+    /// Returns String with header line showing 'tagged entry count / total entries' and
+    /// keybindings, or the pending warning (which is shown only once, cleared after drawing).
+    /// The counts always come first, followed by as many hint items as fit in the screen
+    /// width, most important first.
+    fn make_header_line(&self) -> String {
+        if let Some(warning) = &self.warning {
+            return format!("{} {} ", self.theme.warning, warning);
+        }
+        // position of the cursor among the listed entries, and how many match the filter
+        let position = cmp::min(self.line_idx, self.visible.len());
+        let mut counts = format!(" item {position}/{}", self.visible.len());
+        if self.filter.is_some() {
+            counts.push_str(&format!(", {} matched", self.visible.len()));
+        }
+        if self.options.browse {
+            counts.push_str(&format!(" ({} total)", self.entries.len()));
+        } else {
+            let budget = match (&self.options.budget, self.budget_left()) {
+                (Some(budget), Some(left)) => format!(", {} left", budget.format(left)),
+                _ => String::new(),
+            };
+            counts.push_str(&format!(
+                " ({} selected / {} total{budget})",
+                self.sel_tracker.len(),
+                self.entries.len()
+            ));
+        }
+        let items = if self.options.hint {
+            self.options.keymap.hint_items()
+        } else {
+            Vec::new()
+        };
+        let header = header_text(&counts, &items, self.viewport().0 as usize);
+        format!("{}{header}", self.theme.header)
+    }
+
+    /// Returns Vec<String> with each line to be displayed from the entries in range,
     /// including cursor character '>' positioned in the current line and with
     /// corresponding formatting (one color pair for regular entries and the
-    /// reversed color pair for the header and selected entries).
-    fn make_entries_into_lines(&mut self) -> Vec<String> {
+    /// reversed color pair for the header and selected entries). Right-to-left
+    /// entries are displayed in visual order and right-aligned.
+    fn make_entries_into_lines(&mut self, range: Range<usize>) -> Vec<String> {
+        let width = self.viewport().0 as usize;
         let mut lines = Vec::new();
-        for (idx, entry) in self.entry_list.iter_mut().enumerate() {
-            if self.sel_tracker.contains(&(idx + 2)) {
+        self.on_screen = self.visible[range.clone()].to_vec();
+        for pos in range {
+            let idx = self.visible[pos];
+            let mut entry = self.with_tree_marker(idx, &self.entries.display(idx));
+            // right-to-left entries are reordered and right-aligned, skipping the gutter
+            let rtl = bidi::is_rtl_dominant(&entry);
+            if rtl {
+                entry = bidi::render_rtl(&entry, width.saturating_sub(self.options.gutter.width()));
+            }
+            let selected = self.sel_tracker.contains(&(idx + 2));
+            let current = (pos + 1) == self.line_idx;
+            let (dead, target) = self.path_info(idx);
+            // dead entries keep their style between the highlighted matches
+            let missing = if dead {
+                self.theme.missing.as_str()
+            } else {
+                ""
+            };
+            let matcher = self
+                .search
+                .as_ref()
+                .map(|(_, m)| m)
+                .or(self.filter_matcher.as_ref());
+            if let Some(ranges) = matcher.and_then(|m| m.find(&entry)) {
+                let base = if selected {
+                    self.theme.selected.clone()
+                } else if current {
+                    format!("{}{}{missing}", self.theme.reset, self.theme.cursor)
+                } else {
+                    format!("{}{missing}", self.theme.reset)
+                };
+                entry = highlight_matches(&entry, &ranges, &self.theme.highlight, &base);
+            } else if !selected && !rtl && !dead && self.options.color_depth != ColorDepth::Mono {
+                // input colors are kept on unselected rows, the selected style replaces them
+                if let Some(colored) = self.entries.display_colored(idx) {
+                    entry = format!(
+                        "{}{}",
+                        self.with_tree_marker(idx, &colored),
+                        termion::style::Reset
+                    );
+                } else if let Some(style) = self.ls_style(idx) {
+                    entry = format!("\x1b[{style}m{entry}{}", termion::style::Reset);
+                }
+            }
+            if let Some(target) = target.filter(|_| !rtl) {
+                entry = format!("{entry} → {target}");
+            }
+            if dead && !selected {
+                entry = format!("{missing}{entry}{}", self.theme.reset);
+            }
+            entry = ansi::skip_width(&entry, self.h_offset).into_owned();
+            if self.options.disabled.contains(&idx) {
+                entry = format!(
+                    "{}{entry}{}",
+                    termion::style::Faint,
+                    termion::style::NoFaint
+                );
+            }
+            // the list marks stay in view when scrolling horizontally
+            if let Some(&side) = self.options.diff.as_ref().and_then(|sides| sides.get(idx)) {
+                let style = match side {
+                    Side::Removed => self.theme.removed.as_str(),
+                    Side::Added => self.theme.added.as_str(),
+                    Side::Common => "",
+                };
+                entry = if selected || style.is_empty() {
+                    format!("{} {entry}", side.mark())
+                } else if current {
+                    format!(
+                        "{style}{}{}{} {entry}",
+                        side.mark(),
+                        self.theme.reset,
+                        self.theme.cursor
+                    )
+                } else {
+                    format!("{style}{}{} {entry}", side.mark(), self.theme.reset)
+                };
+            }
+            let mut gutter =
+                self.options
+                    .gutter
+                    .render(current, selected, (pos + 1).abs_diff(self.line_idx));
+            // hint labels cover the gutter, those not starting with the typed letter are hidden
+            if let Some(PendingKey::Hint(first)) = self.pending {
+                let label = self
+                    .hint_label(idx)
+                    .filter(|label| first.is_none_or(|f| label.starts_with(f)))
+                    .unwrap_or_default();
+                let padding = " ".repeat(self.options.gutter.width().saturating_sub(label.width()));
+                gutter = format!(
+                    "{}{label}{}{padding}",
+                    self.theme.highlight, self.theme.reset
+                );
+            }
+            if selected {
                 lines.push(format!(
-                    "{}{}{} {}{}{}",
-                    termion::color::Fg(termion::color::Black),
-                    termion::color::Bg(termion::color::White),
-                    if (idx + 1) == self.line_idx { '>' } else { ' ' },
-                    entry,
-                    termion::color::Fg(termion::color::Reset),
-                    termion::color::Bg(termion::color::Reset)
+                    "{}{gutter}{entry}{}",
+                    self.theme.selected, self.theme.reset
                 ));
-            } else {
+            } else if current && !self.theme.cursor.is_empty() {
                 lines.push(format!(
-                    "{}{}{} {}",
-                    termion::color::Fg(termion::color::Reset),
-                    termion::color::Bg(termion::color::Reset),
-                    if (idx + 1) == self.line_idx { '>' } else { ' ' },
-                    entry
+                    "{}{}{gutter}{entry}{}",
+                    self.theme.reset, self.theme.cursor, self.theme.reset
                 ));
+            } else {
+                lines.push(format!("{}{gutter}{entry}", self.theme.reset));
             };
         }
         lines
     }
 }
 
-/// Returns selected indices, in relation to the provided vector, from the TUI selector.
-pub fn select(entry_list: Vec<String>) -> Result<Option<Vec<usize>>, Box<dyn Error>> {
+impl Drop for SelectorTUI {
+    /// Restores the colors and cursor (and its shape), also when unwinding from a panic (raw
+    /// mode is disabled when the terminal handle is dropped right after).
+    fn drop(&mut self) {
+        self.cancel_preview();
+        self.cancel_filter();
+        let cursor = self.restore_cursor();
+        let _ = write!(self.stdout, "{}{cursor}", termion::style::Reset);
+        let _ = self.stdout.flush();
+    }
+}
+
+/// Returns the text of the header line: the counts, kept whatever the width, followed by the
+/// hint items that fit in the rest of it and a trailing space.
+fn header_text(counts: &str, items: &[String], width: usize) -> String {
+    let hint = fit_hint(items, width.saturating_sub(counts.width()));
+    format!("{counts}{hint} ")
+}
+
+/// Returns the keybindings hint made of the first items that fit in the provided display
+/// width along with the brackets around them and the trailing space, empty if none fits.
+fn fit_hint(items: &[String], width: usize) -> String {
+    let mut hint = String::new();
+    for item in items {
+        let joined = if hint.is_empty() {
+            item.clone()
+        } else {
+            format!("{hint}  {item}")
+        };
+        // two spaces and brackets around the items, and the trailing space of the header
+        if joined.width() + 5 > width {
+            break;
+        }
+        hint = joined;
+    }
+    if hint.is_empty() {
+        hint
+    } else {
+        format!("  [{hint}]")
+    }
+}
+
+/// Returns the preview shown while its command is running.
+fn loading_preview() -> Preview {
+    Preview {
+        text: String::from("loading…"),
+        failed: false,
+    }
+}
+
+/// Returns the preview from the result of its command: its output or, if it failed or
+/// printed nothing, a description of the failure followed by its error output.
+fn describe_preview(output: Result<Output, String>) -> Preview {
+    let output = match output {
+        Ok(output) => output,
+        Err(e) => {
+            return Preview {
+                text: format!("preview command failed: {e}"),
+                failed: true,
+            }
+        }
+    };
+    if output.status.success() && !output.stdout.is_empty() {
+        return Preview {
+            text: String::from_utf8_lossy(&output.stdout).into_owned(),
+            failed: false,
+        };
+    }
+    let summary = if output.status.success() {
+        format!(
+            "preview command printed nothing ({})",
+            describe_status(output.status)
+        )
+    } else {
+        format!(
+            "preview command failed ({})",
+            describe_status(output.status)
+        )
+    };
+    Preview {
+        text: format!("{summary}\n{}", String::from_utf8_lossy(&output.stderr)),
+        failed: true,
+    }
+}
+
+/// Returns the index section of the entry: its first letter in uppercase, ignoring leading
+/// spaces, `None` if it does not start with a letter.
+fn section(entry: &str) -> Option<char> {
+    let first = entry.trim_start().chars().next()?;
+    first
+        .is_ascii_alphabetic()
+        .then(|| first.to_ascii_uppercase())
+}
+
+/// Returns the text with the style `highlight` applied to the provided byte ranges,
+/// switching back to the `base` style after each of them.
+fn highlight_matches(text: &str, ranges: &[Range<usize>], highlight: &str, base: &str) -> String {
+    let mut highlighted = String::new();
+    let mut last_end = 0;
+    for range in ranges {
+        highlighted.push_str(&text[last_end..range.start]);
+        highlighted.push_str(highlight);
+        highlighted.push_str(&text[range.clone()]);
+        highlighted.push_str(base);
+        last_end = range.end;
+    }
+    highlighted.push_str(&text[last_end..]);
+    highlighted
+}
+
+/// Returns selected indices, in relation to the provided entries, from the TUI selector.
+pub fn select(entries: Entries, options: SelectorOptions) -> Result<Outcome, Box<dyn Error>> {
     let mut selection = None;
+    let mut aborted = false;
+    let mut last_key = None;
 
-    let mut tui_selector = SelectorTUI::new(entry_list)?;
-    tui_selector.refresh_content()?;
-    for c in termion::get_tty()?.keys() {
-        match c? {
-            Key::Left | Key::Char('q' | 'h') => {
+    let mut tui_selector = SelectorTUI::new(entries, options)?;
+    // the cursor position is read from the tty, before it is handed to the input thread
+    tui_selector.reserve_window()?;
+    let (tx, events) = mpsc::channel();
+    match tui_selector.options.replay.take() {
+        Some(recording) => {
+            tui_selector.replaying = true;
+            events::replay(tx.clone(), recording);
+        }
+        None => events::listen(tx.clone(), tui_selector.options.append_fifo.as_deref())?,
+    };
+    tui_selector.events_tx = Some(tx);
+    let mut recorder = match &tui_selector.options.record {
+        Some(path) => Some(Recorder::create(path)?),
+        None => None,
+    };
+    if tui_selector.options.inline {
+        tui_selector.open_inline_prompt();
+    }
+    tui_selector.query_background()?;
+    tui_selector.query_cursor_shape()?;
+    let mut pacer = RedrawPacer::default();
+    pacer.draw(&mut tui_selector)?;
+    loop {
+        // a deferred redraw happens once its time comes, unless more events arrive first
+        let received = match pacer.wait() {
+            Some(wait) => events.recv_timeout(wait),
+            None => events.recv().map_err(RecvTimeoutError::from),
+        };
+        let event = match received {
+            Ok(event) => event?,
+            Err(RecvTimeoutError::Timeout) => {
+                pacer.draw(&mut tui_selector)?;
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                tui_selector.quit()?;
+                break;
+            }
+        };
+        if let Some(recorder) = &mut recorder {
+            recorder.record(&event)?;
+        }
+        let key = match event {
+            Event::Key(key) => key,
+            Event::Reload => {
+                tui_selector.reload()?;
+                continue;
+            }
+            Event::Resize => {
+                tui_selector.resize();
+                pacer.redraw(&mut tui_selector)?;
+                continue;
+            }
+            Event::Background(background) => {
+                tui_selector.set_background(background);
+                pacer.redraw(&mut tui_selector)?;
+                continue;
+            }
+            Event::CursorShape(shape) => {
+                tui_selector.cursor_shape = Some(shape);
+                continue;
+            }
+            Event::Append(line) => {
+                tui_selector.append(&line);
+                pacer.redraw(&mut tui_selector)?;
+                continue;
+            }
+            Event::JobDone(pgid, output) => {
+                tui_selector.finish_job(pgid, output);
+                pacer.redraw(&mut tui_selector)?;
+                continue;
+            }
+            Event::End => {
+                tui_selector.quit()?;
+                break;
+            }
+        };
+        last_key = Some(key);
+        // interrupting aborts right away, whatever is open or waiting for confirmation
+        if key == Key::Ctrl('c') {
+            aborted = true;
+            tui_selector.quit()?;
+            break;
+        }
+        // the shown output is closed by any key
+        if tui_selector.output_preview.take().is_some() {
+            pacer.redraw(&mut tui_selector)?;
+            continue;
+        }
+        // the open action menu takes the keys, the action picked in it being run below as if
+        // its key was pressed
+        let picked = match tui_selector.menu_cursor.take() {
+            Some(cursor) => match tui_selector.menu_key(cursor, key) {
+                Some(action) => Some(action),
+                None => {
+                    pacer.redraw(&mut tui_selector)?;
+                    continue;
+                }
+            },
+            None => None,
+        };
+        if let Some(pending) = tui_selector.pending.take() {
+            if let Key::Char(c) = key {
+                tui_selector.run_pending(pending, c);
+            }
+            pacer.redraw(&mut tui_selector)?;
+            continue;
+        }
+        if tui_selector.confirming_quit {
+            tui_selector.confirming_quit = false;
+            if let Key::Char('y' | 'Y') = key {
+                aborted = true;
+                tui_selector.quit()?;
+                break;
+            }
+            pacer.redraw(&mut tui_selector)?;
+            continue;
+        }
+        if let Some(asked) = tui_selector.confirming_run.take() {
+            if let Key::Char('y' | 'Y') = key {
+                last_key = Some(asked);
+                selection = tui_selector.retrieve_selection();
+                tui_selector.quit()?;
+                break;
+            }
+            pacer.redraw(&mut tui_selector)?;
+            continue;
+        }
+        if picked.is_none() && tui_selector.options.expect.contains(&key) {
+            if tui_selector.options.inline {
+                selection = tui_selector.current_entry().map(|entry| vec![entry]);
+                tui_selector.quit()?;
+                break;
+            }
+            if tui_selector.can_run_selection() && tui_selector.confirm_run(key) {
+                selection = tui_selector.retrieve_selection();
+                tui_selector.quit()?;
+                break;
+            }
+            pacer.redraw(&mut tui_selector)?;
+            continue;
+        }
+        if tui_selector.options.inline {
+            match key {
+                Key::Char('\t') => tui_selector.move_down(),
+                Key::BackTab => tui_selector.move_up(),
+                Key::Char('\n')
+                    if tui_selector
+                        .current_entry()
+                        .is_some_and(|e| tui_selector.options.disabled.contains(&e)) => {}
+                Key::Char('\n') => {
+                    selection = tui_selector.current_entry().map(|entry| vec![entry]);
+                    tui_selector.quit()?;
+                    break;
+                }
+                Key::Esc | Key::Ctrl('g') => {
+                    aborted = true;
+                    tui_selector.quit()?;
+                    break;
+                }
+                _ => tui_selector.edit_inline_query(key),
+            }
+            pacer.redraw(&mut tui_selector)?;
+            continue;
+        }
+        if tui_selector.prompt.is_some() {
+            tui_selector.handle_prompt_key(key);
+            pacer.redraw(&mut tui_selector)?;
+            continue;
+        }
+        let action = picked.or_else(|| tui_selector.options.keymap.action(key));
+        // digits not bound to an action make a count, a leading zero being none
+        if let (None, Key::Char(c @ '0'..='9')) = (action, key) {
+            if c != '0' || tui_selector.count.is_some() {
+                tui_selector.push_count_digit(c as usize - '0' as usize);
+                continue;
+            }
+        }
+        if let (Some(count), Some(action)) = (tui_selector.count.take(), action) {
+            if tui_selector.run_counted(action, count) {
+                pacer.redraw(&mut tui_selector)?;
+                continue;
+            }
+        }
+        match action {
+            Some(Action::Quit) if tui_selector.can_quit() => {
+                aborted = true;
                 tui_selector.quit()?;
                 break;
             }
-            Key::Up | Key::Char('k') => tui_selector.move_up(),
-            Key::Down | Key::Char('j') => tui_selector.move_down(),
-            Key::Right | Key::Char('l') => tui_selector.toggle_selection(),
-            Key::Char('a') => tui_selector.select_all(),
-            Key::Char('n') => tui_selector.select_none(),
-            Key::Char('\n') => {
+            // the list grows upwards in the reverse layout
+            Some(Action::Up) if tui_selector.options.reverse => tui_selector.move_down(),
+            Some(Action::Down) if tui_selector.options.reverse => tui_selector.move_up(),
+            Some(Action::Up) => tui_selector.move_up(),
+            Some(Action::Down) => tui_selector.move_down(),
+            Some(Action::Top) => tui_selector.go_top(),
+            Some(Action::Bottom) => tui_selector.go_bottom(),
+            Some(Action::Toggle) => tui_selector.toggle_selection(),
+            Some(Action::SelectAll) => tui_selector.select_all(),
+            Some(Action::SelectNone) => tui_selector.select_none(),
+            Some(Action::SelectPattern) => tui_selector.open_select_pattern_prompt(),
+            Some(Action::Search) => tui_selector.open_search_prompt(),
+            Some(Action::SearchNext) => tui_selector.search_next(true),
+            Some(Action::SearchPrev) => tui_selector.search_next(false),
+            Some(Action::Filter) => tui_selector.open_filter_prompt(),
+            Some(Action::CycleMatchMode) => tui_selector.cycle_match_mode(),
+            Some(Action::PreviewUp) => tui_selector.scroll_preview(false),
+            Some(Action::PreviewDown) => tui_selector.scroll_preview(true),
+            Some(Action::RefreshPreview) => tui_selector.refresh_preview(),
+            Some(Action::Collapse) => tui_selector.collapse(),
+            Some(Action::Expand) => tui_selector.expand(),
+            Some(Action::Reshuffle) => tui_selector.reshuffle(),
+            Some(Action::Jump) => tui_selector.start_jump(),
+            Some(Action::SetMark) => tui_selector.start_set_mark(),
+            Some(Action::GoToMark) => tui_selector.start_go_to_mark(),
+            Some(Action::ToggleIgnored) => tui_selector.toggle_listed(false),
+            Some(Action::ToggleHidden) => tui_selector.toggle_listed(true),
+            Some(Action::Hint) => tui_selector.start_hint(),
+            Some(Action::ShowOutput) => tui_selector.show_output(),
+            Some(Action::Menu) => tui_selector.open_menu(),
+            Some(Action::MoveEntryUp) => tui_selector.move_entry(tui_selector.options.reverse),
+            Some(Action::MoveEntryDown) => tui_selector.move_entry(!tui_selector.options.reverse),
+            Some(Action::ScrollLeft) => tui_selector.scroll_horizontal(false),
+            Some(Action::ScrollRight) => tui_selector.scroll_horizontal(true),
+            #[cfg(feature = "lua")]
+            Some(Action::Script(action)) => tui_selector.run_script_action(action),
+            Some(Action::Accept)
+                if tui_selector.can_run_selection() && tui_selector.confirm_run(key) =>
+            {
                 selection = tui_selector.retrieve_selection();
                 tui_selector.quit()?;
                 break;
             }
             _ => {}
         }
-        tui_selector.refresh_content()?;
+        pacer.redraw(&mut tui_selector)?;
+    }
+    Ok(Outcome {
+        selection,
+        matched: tui_selector.count_matched(),
+        aborted,
+        selected: tui_selector.sel_tracker.iter().map(|i| i - 2).collect(),
+        query: tui_selector
+            .filter
+            .clone()
+            .or_else(|| tui_selector.search.as_ref().map(|(query, _)| query.clone())),
+        cursor: tui_selector.current_entry(),
+        key: last_key,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(items: &[&str]) -> Vec<String> {
+        items.iter().map(|i| i.to_string()).collect()
+    }
+
+    #[test]
+    fn fit_hint_leaves_out_items_past_the_width() {
+        let items = items(&["enter:run", "q:quit"]);
+        // "  [enter:run  q:quit]" and the trailing space of the header
+        let total = 22;
+        assert_eq!(fit_hint(&items, 0), "");
+        assert_eq!(fit_hint(&items, 1), "");
+        assert_eq!(fit_hint(&items, total - 1), "  [enter:run]");
+        assert_eq!(fit_hint(&items, total), "  [enter:run  q:quit]");
+        assert_eq!(fit_hint(&items, total + 10), "  [enter:run  q:quit]");
+    }
+
+    #[test]
+    fn fit_hint_measures_wide_characters() {
+        let items = items(&["選択:選ぶ", "q:quit"]);
+        // each CJK character takes two columns: "  [選択:選ぶ]" is 13 wide
+        assert_eq!(fit_hint(&items, 13), "");
+        assert_eq!(fit_hint(&items, 14), "  [選択:選ぶ]");
+        assert_eq!(fit_hint(&items, 21), "  [選択:選ぶ]");
+        assert_eq!(fit_hint(&items, 22), "  [選択:選ぶ  q:quit]");
+    }
+
+    #[test]
+    fn header_text_always_keeps_the_counts() {
+        let items = items(&["enter:run", "q:quit"]);
+        let counts = " item 1/3 (0 selected / 3 total)";
+        for width in [
+            0,
+            1,
+            counts.width() - 1,
+            counts.width(),
+            counts.width() + 13,
+        ] {
+            assert_eq!(header_text(counts, &items, width), format!("{counts} "));
+        }
+        assert_eq!(
+            header_text(counts, &items, counts.width() + 14),
+            format!("{counts}  [enter:run] ")
+        );
     }
-    Ok(selection)
 }
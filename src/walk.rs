@@ -0,0 +1,111 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::path::{Component, PathBuf};
+
+use globset::GlobBuilder;
+use ignore::WalkBuilder;
+
+/// Characters making a path component a pattern rather than a literal name.
+const GLOB_CHARS: &[char] = &['*', '?', '[', '{'];
+
+/// Files listed with `--glob`, those ignored or hidden being only shown on demand.
+pub struct Listing {
+    /// Whether each file is ignored by a .gitignore or .ignore file (or the git excludes),
+    /// or is in a ".git" directory.
+    ignored: Vec<bool>,
+    /// Whether the name of each file, or of one of its directories, starts with ".".
+    hidden: Vec<bool>,
+    pub show_ignored: bool,
+    pub show_hidden: bool,
+}
+
+impl Listing {
+    /// Returns whether the entry is listed, entries that are not files always being so.
+    pub fn is_shown(&self, idx: usize) -> bool {
+        let ignored = self.ignored.get(idx) == Some(&true);
+        let hidden = self.hidden.get(idx) == Some(&true);
+        (self.show_ignored || !ignored) && (self.show_hidden || !hidden)
+    }
+}
+
+/// Returns the paths of the files matching the glob pattern (e.g., "src/**/*.rs"), sorted
+/// by name, "*" not matching across directories while "**" does, along with which of them
+/// are ignored or hidden. Relative patterns are matched under the current directory, and
+/// only the directory before the first pattern component is walked (ignore files being
+/// read also outside repositories).
+pub fn glob(
+    pattern: &str,
+    show_ignored: bool,
+    show_hidden: bool,
+) -> Result<(Vec<String>, Listing), Box<dyn Error>> {
+    let pattern = pattern.strip_prefix("./").unwrap_or(pattern);
+    let matcher = GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()?
+        .compile_matcher();
+    let root = match literal_prefix(pattern) {
+        "" => ".",
+        root => root,
+    };
+    // the files left by a walk skipping ignored ones are those that are not ignored
+    let mut kept_walker = WalkBuilder::new(root);
+    kept_walker
+        .standard_filters(false)
+        .ignore(true)
+        .git_ignore(true)
+        .git_global(true)
+        .git_exclude(true)
+        .require_git(false)
+        .filter_entry(|entry| entry.file_name() != ".git");
+    let kept: HashSet<PathBuf> = kept_walker
+        .build()
+        .filter_map(Result::ok)
+        .map(|entry| entry.into_path())
+        .collect();
+
+    let mut walker = WalkBuilder::new(root);
+    walker
+        .standard_filters(false)
+        .sort_by_file_name(|a, b| a.cmp(b));
+    let mut paths = Vec::new();
+    let mut listing = Listing {
+        ignored: Vec::new(),
+        hidden: Vec::new(),
+        show_ignored,
+        show_hidden,
+    };
+    // unreadable directories are skipped, as with find's errors
+    for entry in walker.build().filter_map(Result::ok) {
+        if entry.file_type().is_some_and(|t| t.is_dir()) {
+            continue;
+        }
+        let path = entry.path();
+        let shown = path.strip_prefix("./").unwrap_or(path);
+        if !matcher.is_match(shown) {
+            continue;
+        }
+        paths.push(shown.to_string_lossy().into_owned());
+        listing.ignored.push(!kept.contains(path));
+        // the directories written in the pattern count as asked for
+        let below_root = path.strip_prefix(root).unwrap_or(path);
+        listing
+            .hidden
+            .push(below_root.components().any(|c| match c {
+                Component::Normal(name) => name.to_string_lossy().starts_with('.'),
+                _ => false,
+            }));
+    }
+    Ok((paths, listing))
+}
+
+/// Returns the leading directories of the pattern without glob characters, e.g., "src" for
+/// "src/**/*.rs", "/" for "/*.conf", empty for "*.rs".
+fn literal_prefix(pattern: &str) -> &str {
+    let literal = &pattern[..pattern.find(GLOB_CHARS).unwrap_or(pattern.len())];
+    let prefix = &literal[..literal.rfind('/').map_or(0, |slash| slash + 1)];
+    if prefix == "/" {
+        prefix
+    } else {
+        prefix.trim_end_matches('/')
+    }
+}